@@ -0,0 +1,63 @@
+//! Rough timing of evaluating one expression many times, run with `cargo bench`.
+
+use rust_exprs::Expression;
+use std::{
+    collections::VecDeque,
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+const ITERATIONS: u32 = 100_000;
+
+fn bench(name: &str, mut f: impl FnMut()) {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let elapsed: Duration = start.elapsed();
+    println!("{name}: {:?} per evaluation", elapsed / ITERATIONS);
+}
+
+fn main() {
+    let mut expr = Expression::new(
+        "({a} + {b}) * ({c} - {d}) / max({e}, 1) > 10 && abs({f} - {a}) < {b} * 2 || {c} = {d}",
+    )
+    .expect("benchmark expression should be valid");
+    for (name, value) in [
+        ("a", 1.0),
+        ("b", 2.0),
+        ("c", 3.0),
+        ("d", 4.0),
+        ("e", 5.0),
+        ("f", 6.0),
+    ] {
+        expr.update_variable(name, value)
+            .expect("variable should exist");
+    }
+
+    bench("eval", || {
+        black_box(expr.eval().expect("evaluation should succeed"));
+    });
+
+    let mut buffer = VecDeque::new();
+    bench("eval_into", || {
+        black_box(
+            expr.eval_into(&mut buffer)
+                .expect("evaluation should succeed"),
+        );
+    });
+
+    bench("eval_with_slice", || {
+        black_box(
+            expr.eval_with_slice(&[
+                ("a", 1.0),
+                ("b", 2.0),
+                ("c", 3.0),
+                ("d", 4.0),
+                ("e", 5.0),
+                ("f", 6.0),
+            ])
+            .expect("evaluation should succeed"),
+        );
+    });
+}
@@ -0,0 +1,752 @@
+use crate::{
+    emitter::{EmitResult, Emitter, VariableMap},
+    parser::{FunctionType, OperatorType, Rpn, Value},
+    type_checker::VarType,
+};
+
+/// tree form of an expression, reconstructed from its reverse polish notation
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ast {
+    BinaryOp {
+        operator: OperatorType,
+        left: Box<Ast>,
+        right: Box<Ast>,
+    },
+    // postfix operators, e.g. factorial
+    UnaryOp {
+        operator: OperatorType,
+        operand: Box<Ast>,
+    },
+    Number(f32),
+    Boolean(bool),
+    Variable(String),
+    Placeholder(usize),
+    Function {
+        function: FunctionType,
+        args: Vec<Ast>,
+    },
+    List(Vec<Ast>),
+}
+
+impl Ast {
+    /// Builds the tree by running the stack machine on nodes instead of values.
+    /// Returns `None` if the rpn does not reduce to a single node.
+    pub fn from_rpn(rpn: &[Value]) -> Option<Ast> {
+        let mut node_stack: Vec<Ast> = Vec::new();
+
+        for value in rpn {
+            let node = match value {
+                Value::Number(num) => Ast::Number(*num),
+                Value::Boolean(boolean) => Ast::Boolean(*boolean),
+                Value::Variable(name) => Ast::Variable(name.to_owned()),
+                Value::Placeholder(index) => Ast::Placeholder(*index),
+                Value::Operator(op) if op.is_postfix() => Ast::UnaryOp {
+                    operator: op.clone(),
+                    operand: Box::new(node_stack.pop()?),
+                },
+                Value::Operator(op) => {
+                    let right = node_stack.pop()?;
+                    let left = node_stack.pop()?;
+                    Ast::BinaryOp {
+                        operator: op.clone(),
+                        left: Box::new(left),
+                        right: Box::new(right),
+                    }
+                }
+                Value::Function(function, argc) => {
+                    let start = node_stack.len().checked_sub(*argc)?;
+                    Ast::Function {
+                        function: function.clone(),
+                        args: node_stack.split_off(start),
+                    }
+                }
+                Value::List(len) => {
+                    let start = node_stack.len().checked_sub(*len)?;
+                    Ast::List(node_stack.split_off(start))
+                }
+            };
+            node_stack.push(node);
+        }
+
+        if node_stack.len() != 1 {
+            return None;
+        }
+        node_stack.pop()
+    }
+
+    /// Converts the tree back to reverse polish notation.
+    pub fn into_rpn(self) -> Rpn {
+        let mut rpn = vec![];
+        self.push_rpn(&mut rpn);
+        rpn
+    }
+
+    fn push_rpn(self, rpn: &mut Rpn) {
+        match self {
+            Ast::BinaryOp {
+                operator,
+                left,
+                right,
+            } => {
+                left.push_rpn(rpn);
+                right.push_rpn(rpn);
+                rpn.push(Value::Operator(operator));
+            }
+            Ast::UnaryOp { operator, operand } => {
+                operand.push_rpn(rpn);
+                rpn.push(Value::Operator(operator));
+            }
+            Ast::Number(num) => rpn.push(Value::Number(num)),
+            Ast::Boolean(boolean) => rpn.push(Value::Boolean(boolean)),
+            Ast::Variable(name) => rpn.push(Value::Variable(name)),
+            Ast::Placeholder(index) => rpn.push(Value::Placeholder(index)),
+            Ast::Function { function, args } => {
+                let arg_count = args.len();
+                for arg in args {
+                    arg.push_rpn(rpn);
+                }
+                rpn.push(Value::Function(function, arg_count));
+            }
+            Ast::List(elements) => {
+                let len = elements.len();
+                for element in elements {
+                    element.push_rpn(rpn);
+                }
+                rpn.push(Value::List(len));
+            }
+        }
+    }
+
+    /// Orders the operands of commutative operators (`+`, `*`, `&&`, `||`, `=`)
+    /// so that equivalent trees become equal, e.g. `2 + 1` becomes `1 + 2`.
+    /// Other operators are untouched and operands are not regrouped,
+    /// so `(1 + 2) + 3` and `1 + (2 + 3)` stay different.
+    pub fn normalize(self) -> Ast {
+        match self {
+            Ast::BinaryOp {
+                operator,
+                left,
+                right,
+            } => {
+                let (mut left, mut right) = (left.normalize(), right.normalize());
+                if is_commutative(&operator) && left.to_string() > right.to_string() {
+                    std::mem::swap(&mut left, &mut right);
+                }
+                Ast::BinaryOp {
+                    operator,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                }
+            }
+            Ast::UnaryOp { operator, operand } => Ast::UnaryOp {
+                operator,
+                operand: Box::new(operand.normalize()),
+            },
+            Ast::Function { function, args } => Ast::Function {
+                function,
+                args: args.into_iter().map(Ast::normalize).collect(),
+            },
+            Ast::List(elements) => Ast::List(elements.into_iter().map(Ast::normalize).collect()),
+            leaf => leaf,
+        }
+    }
+
+    /// Removes boolean literals from `&&` and `||`: `true && x` and `false || x` become `x`,
+    /// `false && x` becomes `false` and `true || x` becomes `true`, on either side.
+    /// Only applied when `x` is a boolean expression, variables are numbers
+    /// and replacing e.g. `true && {x}` would change the result type.
+    pub fn simplify_boolean(self) -> Ast {
+        match self {
+            Ast::BinaryOp {
+                operator: operator @ (OperatorType::And | OperatorType::Or),
+                left,
+                right,
+            } => {
+                let (left, right) = (left.simplify_boolean(), right.simplify_boolean());
+                // `true` for `||`, `false` for `&&`
+                let decisive = operator == OperatorType::Or;
+                match (left, right) {
+                    (Ast::Boolean(literal), other) | (other, Ast::Boolean(literal))
+                        if other.is_boolean() =>
+                    {
+                        if literal == decisive {
+                            Ast::Boolean(decisive)
+                        } else {
+                            other
+                        }
+                    }
+                    (left, right) => Ast::BinaryOp {
+                        operator,
+                        left: Box::new(left),
+                        right: Box::new(right),
+                    },
+                }
+            }
+            Ast::BinaryOp {
+                operator,
+                left,
+                right,
+            } => Ast::BinaryOp {
+                operator,
+                left: Box::new(left.simplify_boolean()),
+                right: Box::new(right.simplify_boolean()),
+            },
+            Ast::UnaryOp { operator, operand } => Ast::UnaryOp {
+                operator,
+                operand: Box::new(operand.simplify_boolean()),
+            },
+            Ast::Function { function, args } => Ast::Function {
+                function,
+                args: args.into_iter().map(Ast::simplify_boolean).collect(),
+            },
+            Ast::List(elements) => {
+                Ast::List(elements.into_iter().map(Ast::simplify_boolean).collect())
+            }
+            leaf => leaf,
+        }
+    }
+
+    // true if the node always evaluates to a boolean, when it evaluates without errors
+    fn is_boolean(&self) -> bool {
+        match self {
+            Ast::Boolean(_) => true,
+            Ast::BinaryOp { operator, .. } => matches!(
+                operator,
+                OperatorType::And
+                    | OperatorType::Or
+                    | OperatorType::Implies
+                    | OperatorType::LT
+                    | OperatorType::LE
+                    | OperatorType::GT
+                    | OperatorType::GE
+                    | OperatorType::Eq
+                    | OperatorType::In
+            ),
+            Ast::Function { function, .. } => function.result_type() == VarType::Boolean,
+            _ => false,
+        }
+    }
+
+    /// Returns true if a subtree without variables evaluates to `NaN` or an infinity,
+    /// e.g. `0 / 0` in `{x} + 0 / 0` or `log(0)`.
+    pub fn has_non_finite_constant(&self) -> bool {
+        if let Some(EmitResult::Number(num)) = self.constant_value(&VariableMap::new()) {
+            if !num.is_finite() {
+                return true;
+            }
+        }
+
+        match self {
+            Ast::BinaryOp { left, right, .. } => {
+                left.has_non_finite_constant() || right.has_non_finite_constant()
+            }
+            Ast::UnaryOp { operand, .. } => operand.has_non_finite_constant(),
+            Ast::Function { args, .. } | Ast::List(args) => {
+                args.iter().any(Ast::has_non_finite_constant)
+            }
+            Ast::Number(_) | Ast::Boolean(_) | Ast::Variable(_) | Ast::Placeholder(_) => false,
+        }
+    }
+
+    /// Returns true if an operand of `=` contains a division, e.g. `1/3 = 0.333`,
+    /// as the rounding of the quotient makes the comparison unreliable.
+    pub fn has_fragile_comparison(&self) -> bool {
+        match self {
+            Ast::BinaryOp {
+                operator: OperatorType::Eq,
+                left,
+                right,
+            } if left.contains_operator(&OperatorType::Divide)
+                || right.contains_operator(&OperatorType::Divide) =>
+            {
+                true
+            }
+            _ => self.children().into_iter().any(Ast::has_fragile_comparison),
+        }
+    }
+
+    fn contains_operator(&self, op: &OperatorType) -> bool {
+        match self {
+            Ast::BinaryOp { operator, .. } | Ast::UnaryOp { operator, .. } if operator == op => {
+                true
+            }
+            _ => self
+                .children()
+                .into_iter()
+                .any(|child| child.contains_operator(op)),
+        }
+    }
+
+    /// Returns the variables missing from `partial` which can still change the result,
+    /// in order of their first occurrence. Variables in a branch skipped by a constant
+    /// condition are not effective, e.g. `{y}` in `{x} > 0 && {y} > 0` with `x = -1`.
+    pub fn effective_variables(&self, partial: &VariableMap) -> Vec<String> {
+        let mut names = vec![];
+        self.collect_effective(partial, &mut names);
+        names
+    }
+
+    fn collect_effective(&self, partial: &VariableMap, names: &mut Vec<String>) {
+        if self.constant_value(partial).is_some() {
+            return;
+        }
+
+        match self {
+            Ast::Variable(name) => {
+                if !names.contains(name) {
+                    names.push(name.to_owned());
+                }
+            }
+            Ast::BinaryOp { left, right, .. } => {
+                left.collect_effective(partial, names);
+                right.collect_effective(partial, names);
+            }
+            Ast::UnaryOp { operand, .. } => operand.collect_effective(partial, names),
+            Ast::Function {
+                function: FunctionType::If,
+                args,
+            } => match args[0].constant_value(partial) {
+                Some(EmitResult::Boolean(true)) => args[1].collect_effective(partial, names),
+                Some(EmitResult::Boolean(false)) => args[2].collect_effective(partial, names),
+                _ => args
+                    .iter()
+                    .for_each(|arg| arg.collect_effective(partial, names)),
+            },
+            Ast::Function { args, .. } | Ast::List(args) => args
+                .iter()
+                .for_each(|arg| arg.collect_effective(partial, names)),
+            Ast::Number(_) | Ast::Boolean(_) | Ast::Placeholder(_) => {}
+        }
+    }
+
+    /// value of the subtree if it does not depend on the missing variables
+    fn constant_value(&self, partial: &VariableMap) -> Option<EmitResult> {
+        let children: Vec<&Ast> = match self {
+            Ast::Number(num) => return Some(EmitResult::Number(*num)),
+            Ast::Boolean(boolean) => return Some(EmitResult::Boolean(*boolean)),
+            Ast::Variable(name) => return partial.get(name).map(|num| EmitResult::Number(*num)),
+            Ast::Placeholder(_) => return None,
+            Ast::BinaryOp {
+                operator: operator @ (OperatorType::And | OperatorType::Or),
+                left,
+                right,
+            } => {
+                // short-circuit, `false && x` is false and `true || x` is true
+                let decisive = EmitResult::Boolean(*operator == OperatorType::Or);
+                let (left, right) = (left.constant_value(partial), right.constant_value(partial));
+                if left.as_ref() == Some(&decisive) || right.as_ref() == Some(&decisive) {
+                    return Some(decisive);
+                }
+                left?;
+                right?;
+                vec![]
+            }
+            Ast::BinaryOp { left, right, .. } => vec![left, right],
+            Ast::UnaryOp { operand, .. } => vec![operand],
+            Ast::Function {
+                function: FunctionType::If,
+                args,
+            } => {
+                return match args[0].constant_value(partial)? {
+                    EmitResult::Boolean(true) => args[1].constant_value(partial),
+                    EmitResult::Boolean(false) => args[2].constant_value(partial),
+                    EmitResult::Number(_) | EmitResult::List(_) => None,
+                }
+            }
+            Ast::Function { args, .. } | Ast::List(args) => args.iter().collect(),
+        };
+
+        for child in children {
+            child.constant_value(partial)?;
+        }
+        Emitter::new(self.clone().into_rpn())
+            .eval_with(|name| partial.get(name).copied())
+            .ok()
+    }
+
+    /// Differentiates the tree with respect to the variable `var`, other variables
+    /// and placeholders are constants. Supports `+`, `-`, `*`, `/` and `pow` with an exponent
+    /// not depending on `var`. Constant terms are folded, so the derivative of `{x} * {x}`
+    /// is `{x} + {x}` rather than `1 * {x} + {x} * 1`.
+    pub fn derivative(&self, var: &str) -> Result<Ast, DerivativeError> {
+        Ok(match self {
+            Ast::Number(_) | Ast::Placeholder(_) => Ast::Number(0.0),
+            Ast::Variable(name) => Ast::Number(if name == var { 1.0 } else { 0.0 }),
+            Ast::BinaryOp {
+                operator,
+                left,
+                right,
+            } => {
+                let (left_derivative, right_derivative) =
+                    (left.derivative(var)?, right.derivative(var)?);
+                let (left, right) = (left.as_ref().clone(), right.as_ref().clone());
+                match operator {
+                    OperatorType::Plus => sum(left_derivative, right_derivative),
+                    OperatorType::Minus => difference(left_derivative, right_derivative),
+                    OperatorType::Times => sum(
+                        product(left_derivative, right.clone()),
+                        product(left, right_derivative),
+                    ),
+                    OperatorType::Divide => quotient(
+                        difference(
+                            product(left_derivative, right.clone()),
+                            product(left, right_derivative),
+                        ),
+                        product(right.clone(), right),
+                    ),
+                    _ => return Err(DerivativeError::UnsupportedOperator(operator.clone())),
+                }
+            }
+            Ast::Function {
+                function: FunctionType::Pow,
+                args,
+            } if !args[1].contains_variable(var) => {
+                // power rule with the chain rule, n * pow(b, n - 1) * b'
+                let (base, exponent) = (&args[0], &args[1]);
+                let power = Ast::Function {
+                    function: FunctionType::Pow,
+                    args: vec![base.clone(), difference(exponent.clone(), Ast::Number(1.0))],
+                };
+                product(product(exponent.clone(), power), base.derivative(var)?)
+            }
+            Ast::Function { function, .. } => {
+                return Err(DerivativeError::UnsupportedFunction(function.clone()))
+            }
+            Ast::UnaryOp { operator, .. } => {
+                return Err(DerivativeError::UnsupportedOperator(operator.clone()))
+            }
+            Ast::Boolean(_) | Ast::List(_) => return Err(DerivativeError::NotNumber),
+        })
+    }
+
+    fn contains_variable(&self, var: &str) -> bool {
+        match self {
+            Ast::Variable(name) => name == var,
+            _ => self
+                .children()
+                .into_iter()
+                .any(|child| child.contains_variable(var)),
+        }
+    }
+
+    /// Draws the tree with ASCII connectors, one node per line, e.g. for `1 + 2`:
+    /// ```text
+    /// +
+    /// |-- 1
+    /// `-- 2
+    /// ```
+    pub fn render_tree(&self) -> String {
+        let mut rendered = format!("{}\n", self.label());
+        self.render_children("", &mut rendered);
+        rendered
+    }
+
+    // appends the children, `prefix` continues the connectors of the ancestors
+    fn render_children(&self, prefix: &str, rendered: &mut String) {
+        let children = self.children();
+        for (i, child) in children.iter().enumerate() {
+            let (connector, continuation) = if i + 1 == children.len() {
+                ("`-- ", "    ")
+            } else {
+                ("|-- ", "|   ")
+            };
+            rendered.push_str(&format!("{prefix}{connector}{}\n", child.label()));
+            child.render_children(&format!("{prefix}{continuation}"), rendered);
+        }
+    }
+
+    // the node without its children
+    fn label(&self) -> String {
+        match self {
+            Ast::BinaryOp { operator, .. } | Ast::UnaryOp { operator, .. } => operator.to_string(),
+            Ast::Number(num) => num.to_string(),
+            Ast::Boolean(boolean) => boolean.to_string(),
+            Ast::Variable(name) => format!("{{{name}}}"),
+            Ast::Placeholder(_) => "?".to_string(),
+            Ast::Function { function, .. } => format!("{function}()"),
+            Ast::List(_) => "()".to_string(),
+        }
+    }
+
+    fn children(&self) -> Vec<&Ast> {
+        match self {
+            Ast::BinaryOp { left, right, .. } => vec![left, right],
+            Ast::UnaryOp { operand, .. } => vec![operand],
+            Ast::Number(_) | Ast::Boolean(_) | Ast::Variable(_) | Ast::Placeholder(_) => vec![],
+            Ast::Function { args, .. } | Ast::List(args) => args.iter().collect(),
+        }
+    }
+
+    fn fmt_indented(&self, f: &mut std::fmt::Formatter<'_>, depth: usize) -> std::fmt::Result {
+        writeln!(f, "{}{}", "  ".repeat(depth), self.label())?;
+        self.children()
+            .into_iter()
+            .try_for_each(|child| child.fmt_indented(f, depth + 1))
+    }
+}
+
+/// Error of [`Ast::derivative`].
+#[derive(Debug, PartialEq)]
+pub enum DerivativeError {
+    UnsupportedOperator(OperatorType),
+    UnsupportedFunction(FunctionType),
+    // booleans and lists
+    NotNumber,
+}
+
+impl std::fmt::Display for DerivativeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DerivativeError::UnsupportedOperator(op) => {
+                write!(f, "cannot differentiate operator '{op}'")
+            }
+            DerivativeError::UnsupportedFunction(function) => {
+                write!(f, "cannot differentiate function '{function}'")
+            }
+            DerivativeError::NotNumber => write!(f, "cannot differentiate a non-number"),
+        }
+    }
+}
+
+fn binary(operator: OperatorType, left: Ast, right: Ast) -> Ast {
+    Ast::BinaryOp {
+        operator,
+        left: Box::new(left),
+        right: Box::new(right),
+    }
+}
+
+// the following build derivatives, folding numbers and dropping zero terms and unit factors
+fn sum(left: Ast, right: Ast) -> Ast {
+    match (left, right) {
+        (Ast::Number(a), Ast::Number(b)) => Ast::Number(a + b),
+        (Ast::Number(0.0), other) | (other, Ast::Number(0.0)) => other,
+        (left, right) => binary(OperatorType::Plus, left, right),
+    }
+}
+
+fn difference(left: Ast, right: Ast) -> Ast {
+    match (left, right) {
+        (Ast::Number(a), Ast::Number(b)) => Ast::Number(a - b),
+        (left, Ast::Number(0.0)) => left,
+        (left, right) => binary(OperatorType::Minus, left, right),
+    }
+}
+
+fn product(left: Ast, right: Ast) -> Ast {
+    match (left, right) {
+        (Ast::Number(a), Ast::Number(b)) => Ast::Number(a * b),
+        (Ast::Number(0.0), _) | (_, Ast::Number(0.0)) => Ast::Number(0.0),
+        (Ast::Number(1.0), other) | (other, Ast::Number(1.0)) => other,
+        (left, right) => binary(OperatorType::Times, left, right),
+    }
+}
+
+fn quotient(left: Ast, right: Ast) -> Ast {
+    match (left, right) {
+        (Ast::Number(0.0), _) => Ast::Number(0.0),
+        (left, Ast::Number(1.0)) => left,
+        (left, right) => binary(OperatorType::Divide, left, right),
+    }
+}
+
+fn is_commutative(op: &OperatorType) -> bool {
+    matches!(
+        op,
+        OperatorType::Plus
+            | OperatorType::Times
+            | OperatorType::And
+            | OperatorType::Or
+            | OperatorType::Eq
+    )
+}
+
+/// one node per line, children indented by two spaces
+impl std::fmt::Display for Ast {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::tokens_to_rpn, tokenizer::Tokenizer};
+    use pretty_assertions::assert_eq;
+
+    fn ast(expr: &str) -> Option<Ast> {
+        Ast::from_rpn(&tokens_to_rpn(Tokenizer::new().tokenize(expr).unwrap()).unwrap())
+    }
+
+    #[test]
+    fn test_from_rpn() {
+        assert_eq!(
+            ast("abs({x}) < 3!"),
+            Some(Ast::BinaryOp {
+                operator: OperatorType::LT,
+                left: Box::new(Ast::Function {
+                    function: FunctionType::Abs,
+                    args: vec![Ast::Variable("x".to_string())],
+                }),
+                right: Box::new(Ast::UnaryOp {
+                    operator: OperatorType::Factorial,
+                    operand: Box::new(Ast::Number(3.0)),
+                }),
+            })
+        );
+        assert_eq!(
+            Ast::from_rpn(&[Value::Number(1.0), Value::Number(2.0)]),
+            None
+        );
+        assert_eq!(Ast::from_rpn(&[Value::Operator(OperatorType::Plus)]), None);
+    }
+
+    #[test]
+    fn test_into_rpn() {
+        let rpn = tokens_to_rpn(
+            Tokenizer::new()
+                .tokenize("max({x}, 2) * (3 + 1)! < 5 || false")
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(Ast::from_rpn(&rpn).unwrap().into_rpn(), rpn);
+    }
+
+    #[test]
+    fn test_normalize() {
+        let normalized = |expr: &str| ast(expr).unwrap().normalize();
+
+        assert_eq!(normalized("{b} * {a} + 1"), normalized("1 + {a} * {b}"));
+        assert_eq!(
+            normalized("(2 = 1) && max(3 + 1, 1)"),
+            normalized("max(1 + 3, 1) && (1 = 2)")
+        );
+        assert_ne!(normalized("2 - 1"), normalized("1 - 2"));
+        // arguments of functions keep their order
+        assert_ne!(normalized("if(true, 1, 2)"), normalized("if(true, 2, 1)"));
+    }
+
+    #[test]
+    fn test_simplify_boolean() {
+        let simplified = |expr: &str| ast(expr).unwrap().simplify_boolean();
+
+        assert_eq!(simplified("true && {x} > 0"), ast("{x} > 0").unwrap());
+        assert_eq!(simplified("{x} > 0 && true"), ast("{x} > 0").unwrap());
+        assert_eq!(simplified("false || {x} > 0"), ast("{x} > 0").unwrap());
+        assert_eq!(simplified("true || {x} > 0"), Ast::Boolean(true));
+        assert_eq!(simplified("{x} > 0 && false"), Ast::Boolean(false));
+        assert_eq!(
+            simplified("({x} > 0 || false) && (true && {y} < 1)"),
+            ast("{x} > 0 && {y} < 1").unwrap()
+        );
+        assert_eq!(
+            simplified("if(true && between({x}, 0, 1), 1, 2)"),
+            ast("if(between({x}, 0, 1), 1, 2)").unwrap()
+        );
+        // a number operand is a type error, which is kept
+        assert_eq!(simplified("true && {x}"), ast("true && {x}").unwrap());
+    }
+
+    #[test]
+    fn test_has_non_finite_constant() {
+        let non_finite = |expr: &str| ast(expr).unwrap().has_non_finite_constant();
+
+        assert!(non_finite("1 + 0/0"));
+        assert!(non_finite("{x} * sqrt(0 - 1)"));
+        assert!(non_finite("log(0) < {x}"));
+        // the comparison is finite, but its operand is not
+        assert!(non_finite("0/0 > 1"));
+        assert!(!non_finite("{x} / 0"));
+        assert!(!non_finite("sqrt(4) + log(1)"));
+    }
+
+    #[test]
+    fn test_has_fragile_comparison() {
+        let fragile = |expr: &str| ast(expr).unwrap().has_fragile_comparison();
+
+        assert!(fragile("1/3 = 0.333"));
+        assert!(fragile("{x} = abs({y} / 2) + 1"));
+        assert!(fragile("true && ({x} = {y} / 2)"));
+        assert!(!fragile("{x} = {y} * 2"));
+        assert!(!fragile("1/3 < 0.34"));
+    }
+
+    #[test]
+    fn test_effective_variables() {
+        let effective = |expr: &str, partial: &[(&str, f32)]| {
+            let partial: VariableMap = partial
+                .iter()
+                .map(|(name, value)| (name.to_string(), *value))
+                .collect();
+            ast(expr).unwrap().effective_variables(&partial)
+        };
+
+        assert_eq!(effective("{x} + {y} * {x}", &[]), vec!["x", "y"]);
+        assert_eq!(effective("{x} + {y} * {x}", &[("x", 1.0)]), vec!["y"]);
+        assert_eq!(
+            effective("{x} > 0 && {y} > 0", &[("x", -1.0)]),
+            Vec::<String>::new()
+        );
+        assert_eq!(effective("{x} > 0 && {y} > 0", &[("x", 1.0)]), vec!["y"]);
+        assert_eq!(
+            effective("{y} < 1 || {x} > 0", &[("x", 1.0)]),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            effective("if({c} > 0, {a}, {b}) + {d}", &[("c", 0.0)]),
+            vec!["b", "d"]
+        );
+        assert_eq!(effective("if({c} > 0, {a}, {b})", &[]), vec!["c", "a", "b"]);
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            ast("(1 + {x}) * sign(2) = true").unwrap().to_string(),
+            "=\n  *\n    +\n      1\n      {x}\n    sign()\n      2\n  true\n"
+        );
+    }
+
+    #[test]
+    fn test_derivative() {
+        let derivative = |expr: &str| ast(expr).unwrap().derivative("x");
+
+        assert_eq!(derivative("{x} * {x}"), Ok(ast("{x} + {x}").unwrap()));
+        assert_eq!(derivative("3 * {x} + {y}"), Ok(Ast::Number(3.0)));
+        assert_eq!(derivative("{y} / 2"), Ok(Ast::Number(0.0)));
+        assert_eq!(
+            derivative("pow({x}, 3)"),
+            Ok(ast("3 * pow({x}, 2)").unwrap())
+        );
+        assert_eq!(
+            derivative("{x} > 1"),
+            Err(DerivativeError::UnsupportedOperator(OperatorType::GT))
+        );
+        assert_eq!(
+            derivative("pow(2, {x})"),
+            Err(DerivativeError::UnsupportedFunction(FunctionType::Pow))
+        );
+        assert_eq!(
+            derivative("{x}!"),
+            Err(DerivativeError::UnsupportedOperator(
+                OperatorType::Factorial
+            ))
+        );
+        assert_eq!(derivative("true"), Err(DerivativeError::NotNumber));
+    }
+
+    #[test]
+    fn test_render_tree() {
+        assert_eq!(
+            ast("(1 + {x}) * sign(2) = true").unwrap().render_tree(),
+            "=\n\
+             |-- *\n\
+             |   |-- +\n\
+             |   |   |-- 1\n\
+             |   |   `-- {x}\n\
+             |   `-- sign()\n\
+             |       `-- 2\n\
+             `-- true\n"
+        );
+    }
+}
@@ -0,0 +1,256 @@
+use crate::{
+    emitter::{EvalOptions, VariableMap},
+    parser::OperatorType,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+/// function taking the evaluated arguments in order
+pub type CustomFunction = Arc<dyn Fn(&[f32]) -> f32 + Send + Sync>;
+
+/// Functions callable from expressions in addition to the built-in ones.
+#[derive(Clone, Default)]
+pub struct FunctionRegistry {
+    // name -> arity and the function
+    functions: HashMap<String, (usize, CustomFunction)>,
+}
+
+impl std::fmt::Debug for FunctionRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(
+                self.functions
+                    .iter()
+                    .map(|(name, (arity, _))| (name, arity)),
+            )
+            .finish()
+    }
+}
+
+impl FunctionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a function taking `arity` arguments, replacing a function with the same name.
+    /// Built-in functions cannot be replaced.
+    pub fn register<F>(&mut self, name: &str, arity: usize, function: F)
+    where
+        F: Fn(&[f32]) -> f32 + Send + Sync + 'static,
+    {
+        self.functions
+            .insert(name.to_owned(), (arity, Arc::new(function)));
+    }
+
+    pub fn arity(&self, name: &str) -> Option<usize> {
+        self.functions.get(name).map(|(arity, _)| *arity)
+    }
+
+    /// returns `None` if the function is not registered
+    pub fn call(&self, name: &str, args: &[f32]) -> Option<f32> {
+        self.functions.get(name).map(|(_, function)| function(args))
+    }
+}
+
+/// binary operator taking the evaluated left and right operands
+pub type CustomOperator = Arc<dyn Fn(f32, f32) -> f32 + Send + Sync>;
+
+/// How a chain of operators with the same priority is grouped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Associativity {
+    /// `a op b op c` is `(a op b) op c`, like all built-in operators
+    #[default]
+    Left,
+    /// `a op b op c` is `a op (b op c)`
+    Right,
+}
+
+/// Binary operators usable in expressions in addition to the built-in ones.
+#[derive(Clone, Default)]
+pub struct OperatorTable {
+    // symbol -> priority, associativity and the operator
+    operators: HashMap<String, (u32, Associativity, CustomOperator)>,
+}
+
+impl std::fmt::Debug for OperatorTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(
+                self.operators
+                    .iter()
+                    .map(|(symbol, (priority, associativity, _))| {
+                        (symbol, (priority, associativity))
+                    }),
+            )
+            .finish()
+    }
+}
+
+impl OperatorTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers an operator on numbers, replacing an operator with the same symbol.
+    /// The priority is compared with [`OperatorType::priority`] of the built-in operators,
+    /// e.g. `8` binds like `*`. The symbol is matched before the built-in operators,
+    /// so it can start with one of them, e.g. `**`, but it cannot contain whitespace.
+    pub fn register<F>(
+        &mut self,
+        symbol: &str,
+        priority: u32,
+        associativity: Associativity,
+        operator: F,
+    ) where
+        F: Fn(f32, f32) -> f32 + Send + Sync + 'static,
+    {
+        self.operators.insert(
+            symbol.to_owned(),
+            (priority, associativity, Arc::new(operator)),
+        );
+    }
+
+    /// returns `None` if no operator has the symbol
+    pub fn operator(&self, symbol: &str) -> Option<OperatorType> {
+        self.operators
+            .get(symbol)
+            .map(|(priority, associativity, _)| OperatorType::Custom {
+                symbol: symbol.to_owned(),
+                priority: *priority,
+                associativity: *associativity,
+            })
+    }
+
+    pub fn symbols(&self) -> HashSet<String> {
+        self.operators.keys().cloned().collect()
+    }
+
+    /// returns `None` if no operator has the symbol
+    pub fn call(&self, symbol: &str, first: f32, second: f32) -> Option<f32> {
+        self.operators
+            .get(symbol)
+            .map(|(_, _, operator)| operator(first, second))
+    }
+}
+
+/// Source of variable values, e.g. the fields of a struct,
+/// see [`crate::Expression::eval_resolver`].
+pub trait VariableResolver {
+    /// returns `None` if there is no variable with the name
+    fn resolve(&self, name: &str) -> Option<f32>;
+}
+
+impl VariableResolver for VariableMap {
+    fn resolve(&self, name: &str) -> Option<f32> {
+        self.get(name).copied()
+    }
+}
+
+/// What happens when a variable has no value and is not the left operand of `??`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnboundVariables {
+    /// the evaluation fails with [`crate::EvalError::UnboundVariable`]
+    #[default]
+    Error,
+    /// the variable is `0` in arithmetic, operands of `&&`, `||`, `=>`,
+    /// arguments of `first_true` and the condition of `if` still fail
+    ZeroInArithmetic,
+}
+
+/// Everything an expression is evaluated with, see [`crate::Expression::eval_in`].
+#[derive(Debug, Clone, Default)]
+pub struct EvalContext {
+    pub variables: VariableMap,
+
+    /// Values of variables missing from `variables`, shared between evaluations.
+    pub constants: HashMap<String, f32>,
+
+    /// Custom functions, the expression has to be built with the same names and arities,
+    /// see [`crate::expression::ExpressionBuilder::functions`].
+    pub functions: FunctionRegistry,
+
+    pub options: EvalOptions,
+
+    pub unbound_variables: UnboundVariables,
+}
+
+impl EvalContext {
+    /// value of the variable, or of the constant with the same name
+    pub fn lookup(&self, name: &str) -> Option<f32> {
+        self.variables
+            .get(name)
+            .or_else(|| self.constants.get(name))
+            .copied()
+    }
+}
+
+impl VariableResolver for EvalContext {
+    fn resolve(&self, name: &str) -> Option<f32> {
+        self.lookup(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Associativity, EvalContext, FunctionRegistry, OperatorTable, VariableResolver};
+    use crate::parser::OperatorType;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_function_registry() {
+        let mut functions = FunctionRegistry::new();
+        functions.register("sum3", 3, |args| args.iter().sum());
+
+        assert_eq!(functions.arity("sum3"), Some(3));
+        assert_eq!(functions.call("sum3", &[1.0, 2.0, 3.0]), Some(6.0));
+        assert_eq!(functions.arity("abs"), None);
+        assert_eq!(functions.call("foo", &[]), None);
+    }
+
+    #[test]
+    fn test_operator_table() {
+        let mut operators = OperatorTable::new();
+        operators.register("%%", 8, Associativity::Left, |a, b| (a + b) / 2.0);
+
+        assert_eq!(
+            operators.operator("%%"),
+            Some(OperatorType::Custom {
+                symbol: "%%".to_string(),
+                priority: 8,
+                associativity: Associativity::Left,
+            })
+        );
+        assert_eq!(operators.call("%%", 4.0, 6.0), Some(5.0));
+        assert_eq!(operators.operator("+"), None);
+        assert_eq!(operators.call("**", 1.0, 2.0), None);
+    }
+
+    #[test]
+    fn test_lookup() {
+        let ctx = EvalContext {
+            variables: HashMap::from([("x".to_string(), 1.0)]),
+            constants: HashMap::from([("x".to_string(), 2.0), ("g".to_string(), 9.81)]),
+            ..Default::default()
+        };
+
+        assert_eq!(ctx.lookup("x"), Some(1.0));
+        assert_eq!(ctx.lookup("g"), Some(9.81));
+        assert_eq!(ctx.lookup("y"), None);
+    }
+
+    #[test]
+    fn test_variable_resolver() {
+        let vars = HashMap::from([("x".to_string(), 1.0)]);
+        assert_eq!(vars.resolve("x"), Some(1.0));
+        assert_eq!(vars.resolve("y"), None);
+
+        let ctx = EvalContext {
+            constants: HashMap::from([("g".to_string(), 9.81)]),
+            ..Default::default()
+        };
+        assert_eq!(ctx.resolve("g"), Some(9.81));
+    }
+}
@@ -1,12 +1,105 @@
-use crate::parser::{OperatorType, Rpn, Value};
-use std::collections::{HashMap, VecDeque};
+use crate::{
+    context::{EvalContext, FunctionRegistry, OperatorTable, UnboundVariables},
+    parser::{FunctionType, OperatorType, Rpn, Value},
+    type_checker::{ResultType, VarType},
+};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 pub type VariableMap = HashMap<String, f32>;
 
-#[derive(Clone, Copy, PartialEq, Debug)]
-pub enum EmitResult {
-    Number(f32),
+/// `N` is the number type, see [`crate::NumEmitter`]
+#[derive(Clone, PartialEq, Debug)]
+pub enum EmitResult<N = f32> {
+    Number(N),
     Boolean(bool),
+    /// parenthesized comma-separated numbers, e.g. `(1, 2, 3)`
+    List(Vec<N>),
+}
+
+impl<N> EmitResult<N> {
+    pub fn result_type(&self) -> ResultType {
+        match self {
+            EmitResult::Number(_) => VarType::Number,
+            EmitResult::Boolean(_) => VarType::Boolean,
+            EmitResult::List(_) => VarType::List,
+        }
+    }
+}
+
+impl EmitResult {
+    /// Formats numbers with `digits` significant figures, e.g. `3.14` for `3.14159` and 3 digits.
+    /// Exponents below -4 or at least `digits` are written in scientific notation, e.g. `1.23e8`.
+    /// Booleans are formatted as `true` or `false` and lists as `[1.00, 2.00]`.
+    pub fn format_sig(&self, digits: usize) -> String {
+        let num = match self {
+            EmitResult::Number(num) => *num as f64,
+            EmitResult::Boolean(boolean) => return boolean.to_string(),
+            EmitResult::List(nums) => {
+                let nums: Vec<String> = nums
+                    .iter()
+                    .map(|num| EmitResult::Number(*num).format_sig(digits))
+                    .collect();
+                return format!("[{}]", nums.join(", "));
+            }
+        };
+        if !num.is_finite() {
+            return num.to_string();
+        }
+
+        let digits = digits.max(1);
+        // the exponent is taken after rounding, 9.996 has to be written as 10.0
+        let scientific = format!("{:.*e}", digits - 1, num);
+        let exponent: i32 = scientific
+            .split_once('e')
+            .and_then(|(_, exponent)| exponent.parse().ok())
+            .unwrap_or(0);
+
+        if exponent < -4 || exponent >= digits as i32 {
+            scientific
+        } else {
+            format!("{:.*}", (digits as i32 - 1 - exponent) as usize, num)
+        }
+    }
+
+    /// true for numbers without a fractional part, e.g. `2` from `4 / 2`
+    pub fn is_integer(&self) -> bool {
+        self.as_i64().is_some()
+    }
+
+    /// the number if it is whole and fits `i64`, `None` for booleans and lists
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            // `i64::MAX as f32` rounds up to 2^63, which does not fit
+            EmitResult::Number(num)
+                if num.fract() == 0.0 && num >= i64::MIN as f32 && num < i64::MAX as f32 =>
+            {
+                Some(num as i64)
+            }
+            _ => None,
+        }
+    }
+
+    /// with coercion, booleans are converted to `1` or `0`
+    fn to_number(&self, coercion: bool) -> Option<f32> {
+        match *self {
+            EmitResult::Number(num) => Some(num),
+            EmitResult::Boolean(boolean) if coercion => Some(if boolean { 1.0 } else { 0.0 }),
+            EmitResult::Boolean(_) | EmitResult::List(_) => None,
+        }
+    }
+
+    /// with coercion, nonzero numbers are converted to `true`
+    fn to_boolean(&self, coercion: bool) -> Option<bool> {
+        match *self {
+            EmitResult::Boolean(boolean) => Some(boolean),
+            EmitResult::Number(num) if coercion => Some(num != 0.0),
+            EmitResult::Number(_) | EmitResult::List(_) => None,
+        }
+    }
 }
 
 impl From<EmitResult> for bool {
@@ -14,6 +107,7 @@ impl From<EmitResult> for bool {
         match value {
             EmitResult::Boolean(val) => val,
             EmitResult::Number(_) => panic!("value is number, not a boolean"),
+            EmitResult::List(_) => panic!("value is list, not a boolean"),
         }
     }
 }
@@ -23,14 +117,115 @@ impl From<EmitResult> for f32 {
         match value {
             EmitResult::Number(val) => val,
             EmitResult::Boolean(_) => panic!("value is boolean, not a number"),
+            EmitResult::List(_) => panic!("value is list, not a number"),
+        }
+    }
+}
+
+// value a variable can be bound to
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Literal {
+    Number(f32),
+    Boolean(bool),
+}
+
+impl From<f32> for Literal {
+    fn from(value: f32) -> Self {
+        Literal::Number(value)
+    }
+}
+
+impl From<Literal> for Value {
+    fn from(value: Literal) -> Self {
+        match value {
+            Literal::Number(num) => Value::Number(num),
+            Literal::Boolean(boolean) => Value::Boolean(boolean),
         }
     }
 }
 
+/// Evaluation of binary operators, implement it to customize how operators evaluate,
+/// e.g. saturating addition. Every method defaults to the built-in evaluation.
+pub trait OperatorEval: std::fmt::Debug + Send + Sync {
+    /// `+`, `-`, `*`, `/` and `&`
+    fn eval_nums(&self, op: &OperatorType, first: f32, second: f32) -> f32 {
+        op.eval_nums(first, second)
+    }
+
+    /// `<`, `<=`, `>`, `>=` and `=`
+    fn eval_comparison(&self, op: &OperatorType, first: f32, second: f32) -> bool {
+        op.eval_comparison(first, second)
+    }
+
+    /// `&&`, `||` and `=>`
+    fn eval_conditional(&self, op: &OperatorType, first: bool, second: bool) -> bool {
+        op.eval_conditional(first, second)
+    }
+}
+
+#[derive(Debug)]
+struct DefaultOperatorEval;
+
+impl OperatorEval for DefaultOperatorEval {}
+
+/// Unit of the argument of `sin`, `cos` and `tan`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AngleMode {
+    #[default]
+    Radians,
+    Degrees,
+}
+
+/// Modes changing how the emitter evaluates operators.
+#[derive(Debug, Clone, Default)]
+pub struct EvalOptions {
+    /// Rounds both operands of `=` to the nearest integer before comparing them.
+    /// Halfway values are rounded away from zero, so `2.5` becomes `3`.
+    pub integer_equality: bool,
+
+    /// Converts booleans to `1` or `0` where numbers are expected
+    /// and nonzero numbers to `true` where booleans are expected.
+    pub coercion: bool,
+
+    /// Fails with [`EvalError::NaNComparison`] when a comparison operand is `NaN`,
+    /// instead of evaluating the comparison to `false`, and with [`EvalError::InvalidLogarithm`]
+    /// for logarithms of non-positive numbers instead of returning `NaN` or `-inf`.
+    pub strict_nan: bool,
+
+    pub angle_mode: AngleMode,
+
+    /// Replaces `-0` with `0` after every step, so that e.g. `1 / {x}` is `inf` for `x = -0`.
+    /// By default the sign of zero is kept as in IEEE 754 arithmetic,
+    /// it shows in division while comparisons treat both zeros as equal.
+    pub unsigned_zero: bool,
+
+    /// Fails with [`EvalError::StepLimitExceeded`] before evaluating an rpn
+    /// of more values, every number, variable, operator and function is a step.
+    pub max_steps: Option<usize>,
+
+    /// Fails with [`EvalError::Timeout`] when the evaluation takes longer, the time is checked
+    /// after every step, so a slow custom function is not interrupted.
+    pub timeout: Option<Duration>,
+
+    /// Custom operator evaluation, the built-in one is used if `None`.
+    pub operator_eval: Option<Arc<dyn OperatorEval>>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Emitter {
     rpn: Rpn,
     no_var_rpn: Option<Rpn>,
+    // name table, every variable in order of the first occurrence with its rpn indices
+    variables: Vec<(String, Vec<usize>)>,
+    // rpn index of every `?`, in order
+    placeholders: Vec<usize>,
+    // rpn index of a variable which is the left operand of `??`, and the index of the `??`
+    coalesce_targets: HashMap<usize, usize>,
+    // rpn indices of variables used as conditions, see `boolean_variables`
+    boolean_variables: HashSet<usize>,
+    options: EvalOptions,
+    functions: FunctionRegistry,
+    operators: OperatorTable,
 }
 
 #[derive(Debug, PartialEq)]
@@ -38,6 +233,59 @@ pub enum EvalError {
     NoVariables,
     NotEnoughValues,
     TooMuchValues,
+    ArgumentTypeMismatch {
+        function: FunctionType,
+        expected: VarType,
+        found: VarType,
+    },
+    WrongResultType {
+        expected: ResultType,
+        found: ResultType,
+    },
+    // comparison operator with a NaN operand in strict mode
+    NaNComparison(OperatorType),
+    // factorial of a negative or non-integer number
+    InvalidFactorial(f32),
+    // contains the name of the variable the lookup has no value for
+    UnboundVariable(String),
+    // contains the name of a custom function missing from the registry
+    UnknownFunction(String),
+    // contains the symbol of a custom operator missing from the operator table
+    UnknownOperator(String),
+    // contains the type of a list element which is not a number
+    InvalidListElement(VarType),
+    // operand types the operator cannot be applied to
+    TypeMismatch {
+        operator: OperatorType,
+        left: VarType,
+        right: VarType,
+    },
+    // the expression evaluated to `NaN` where a number is required
+    NaNResult,
+    // bounds of `Expression::eval_clamped` with `min > max` or a `NaN` bound
+    InvalidBounds {
+        min: f32,
+        max: f32,
+    },
+    // postfix operator or parenthesis evaluated with two operands
+    NotBinaryOperator(OperatorType),
+    // contains the operator or function name which needs `f32` operands
+    UnsupportedNumber(String),
+    // integer arithmetic overflowed or divided by zero
+    Overflow(OperatorType),
+    // non-positive logarithm argument in strict mode
+    InvalidLogarithm(f32),
+    // contains the step limit of the options
+    StepLimitExceeded(usize),
+    // contains the index of a `?` evaluated without `eval_positional`
+    UnfilledPlaceholder(usize),
+    // number of placeholders and of values passed to `eval_positional`
+    PlaceholderCount {
+        expected: usize,
+        found: usize,
+    },
+    // contains the timeout of the options
+    Timeout(Duration),
 }
 
 impl std::fmt::Display for EvalError {
@@ -48,138 +296,817 @@ impl std::fmt::Display for EvalError {
             }
             EvalError::NotEnoughValues => write!(f, "not enough values entered"),
             EvalError::TooMuchValues => write!(f, "too much values entered"),
+            EvalError::ArgumentTypeMismatch {
+                function,
+                expected,
+                found,
+            } => write!(
+                f,
+                "function `{function}` expects {expected} arguments, found {found}"
+            ),
+            EvalError::WrongResultType { expected, found } => {
+                write!(f, "expected {expected} result, found {found}")
+            }
+            EvalError::NaNComparison(op) => {
+                write!(f, "operator `{op}` compared a NaN value")
+            }
+            EvalError::InvalidFactorial(num) => {
+                write!(f, "factorial of {num} is not defined")
+            }
+            EvalError::UnboundVariable(name) => {
+                write!(f, "no value provided for variable {name}")
+            }
+            EvalError::UnknownFunction(name) => {
+                write!(f, "function `{name}` is not registered")
+            }
+            EvalError::UnknownOperator(symbol) => {
+                write!(f, "operator `{symbol}` is not registered")
+            }
+            EvalError::InvalidListElement(found) => {
+                write!(f, "list elements have to be numbers, found {found}")
+            }
+            EvalError::NaNResult => write!(f, "expression evaluated to NaN"),
+            EvalError::InvalidBounds { min, max } => {
+                write!(f, "invalid bounds: minimum {min} and maximum {max}")
+            }
+            EvalError::NotBinaryOperator(op) => {
+                write!(f, "operator `{op}` does not take two operands")
+            }
+            EvalError::UnsupportedNumber(name) => {
+                write!(f, "`{name}` can only be evaluated with f32 numbers")
+            }
+            EvalError::Overflow(op) => {
+                write!(f, "operator `{op}` overflowed or divided by zero")
+            }
+            EvalError::InvalidLogarithm(num) => {
+                write!(f, "logarithm of {num} is not defined")
+            }
+            EvalError::StepLimitExceeded(max) => {
+                write!(f, "evaluation takes more than {max} steps")
+            }
+            EvalError::UnfilledPlaceholder(index) => {
+                write!(f, "placeholder {index} has no value")
+            }
+            EvalError::PlaceholderCount { expected, found } => {
+                write!(f, "expected {expected} placeholder values, found {found}")
+            }
+            EvalError::Timeout(timeout) => {
+                write!(f, "evaluation takes longer than {timeout:?}")
+            }
+            EvalError::TypeMismatch {
+                operator,
+                left,
+                right,
+            } => write!(
+                f,
+                "operator `{operator}` cannot be applied to {left} and {right}"
+            ),
         }
     }
 }
 
 #[derive(Debug, PartialEq)]
 pub enum BindVariablesError {
-    // name of the variable as parameter
-    VariableDoesNotExist(String),
+    // names of the missing variables, in order of their first occurrence
+    VariablesDoNotExist(Vec<String>),
+    // number of values passed to `bind_values` and of variables
+    WrongValueCount { expected: usize, found: usize },
+    // the value passed to `Expression::bind_json` is not an object
+    NotAnObject,
+    // name of a variable whose JSON value is neither a number nor a boolean
+    UnsupportedJsonValue(String),
 }
 
 impl std::fmt::Display for BindVariablesError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            BindVariablesError::VariableDoesNotExist(name) => {
-                write!(f, "variable {name} does not exist")
+            BindVariablesError::VariablesDoNotExist(names) => {
+                write!(f, "variables {} do not exist", names.join(", "))
+            }
+            BindVariablesError::WrongValueCount { expected, found } => {
+                write!(f, "expected {expected} variable values, found {found}")
+            }
+            BindVariablesError::NotAnObject => write!(f, "variables have to be a JSON object"),
+            BindVariablesError::UnsupportedJsonValue(name) => {
+                write!(f, "variable {name} has to be a JSON number or boolean")
             }
         }
     }
 }
 
+/// Calls `visit` with the index of every operator, function and list in the rpn,
+/// and the rpn index where each of its operands starts.
+fn visit_operands<N>(rpn: &Rpn<N>, mut visit: impl FnMut(usize, &[usize])) {
+    // start index of every operand on the value stack
+    let mut starts: Vec<usize> = vec![];
+
+    for (index, value) in rpn.iter().enumerate() {
+        let operand_count = match value {
+            Value::Operator(op) if op.is_postfix() => 1,
+            Value::Operator(_) => 2,
+            Value::Function(_, arg_count) | Value::List(arg_count) => *arg_count,
+            _ => {
+                starts.push(index);
+                continue;
+            }
+        };
+        let Some(first) = starts.len().checked_sub(operand_count) else {
+            // invalid rpn, fails during evaluation
+            return;
+        };
+        visit(index, &starts[first..]);
+
+        let start = starts.get(first).copied().unwrap_or(index);
+        starts.truncate(first);
+        starts.push(start);
+    }
+}
+
+/// Operands of the value at `index` which are a single variable,
+/// as the operand position and the rpn index of the variable.
+fn variable_operands<'a, N>(
+    rpn: &'a Rpn<N>,
+    index: usize,
+    operands: &'a [usize],
+) -> impl Iterator<Item = (usize, usize)> + 'a {
+    operands
+        .iter()
+        .enumerate()
+        .filter_map(move |(position, start)| {
+            let end = operands.get(position + 1).copied().unwrap_or(index);
+            let is_variable = end == start + 1 && matches!(rpn[*start], Value::Variable(_));
+            is_variable.then_some((position, *start))
+        })
+}
+
+/// Maps variables which are the whole left operand of `??` to the index of the `??`.
+pub(crate) fn coalesce_targets<N>(rpn: &Rpn<N>) -> HashMap<usize, usize> {
+    let mut targets = HashMap::new();
+    visit_operands(rpn, |index, operands| {
+        if let Value::Operator(OperatorType::Coalesce) = rpn[index] {
+            if let Some((0, left)) = variable_operands(rpn, index, operands).next() {
+                targets.insert(left, index);
+            }
+        }
+    });
+
+    targets
+}
+
+/// rpn indices of variables which are a whole operand of `&&`, `||` or `=>`,
+/// an argument of `first_true` or the condition of `if`.
+pub(crate) fn boolean_variables<N>(rpn: &Rpn<N>) -> HashSet<usize> {
+    let mut variables = HashSet::new();
+    visit_operands(rpn, |index, operands| {
+        let only_first = match rpn[index] {
+            Value::Operator(OperatorType::And | OperatorType::Or | OperatorType::Implies)
+            | Value::Function(FunctionType::FirstTrue, _) => false,
+            Value::Function(FunctionType::If, _) => true,
+            _ => return,
+        };
+        variables.extend(
+            variable_operands(rpn, index, operands)
+                .filter(|(position, _)| !only_first || *position == 0)
+                .map(|(_, variable)| variable),
+        );
+    });
+
+    variables
+}
+
 impl Emitter {
     pub fn new(rpn: Rpn) -> Self {
-        let contains_variable = rpn.iter().any(|value| matches!(value, Value::Variable(_)));
+        let mut variables: Vec<(String, Vec<usize>)> = vec![];
+        for (index, value) in rpn.iter().enumerate() {
+            if let Value::Variable(name) = value {
+                match variables.iter_mut().find(|(known, _)| known == name) {
+                    Some((_, positions)) => positions.push(index),
+                    None => variables.push((name.to_owned(), vec![index])),
+                }
+            }
+        }
 
-        let no_var_rpn = if contains_variable {
-            None
-        } else {
+        let placeholders = rpn
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| matches!(value, Value::Placeholder(_)))
+            .map(|(index, _)| index)
+            .collect();
+
+        let no_var_rpn = if variables.is_empty() {
             Some(rpn.to_owned())
+        } else {
+            None
         };
 
-        Self { rpn, no_var_rpn }
+        Self {
+            coalesce_targets: coalesce_targets(&rpn),
+            boolean_variables: boolean_variables(&rpn),
+            rpn,
+            no_var_rpn,
+            variables,
+            placeholders,
+            options: EvalOptions::default(),
+            functions: FunctionRegistry::default(),
+            operators: OperatorTable::default(),
+        }
     }
 
-    /// replaces variable values with numbers
-    pub fn bind_variables(&mut self, var_map: &VariableMap) -> Result<(), BindVariablesError> {
-        self.no_var_rpn = Some(
-            self.rpn
+    pub fn rpn(&self) -> &Rpn {
+        &self.rpn
+    }
+
+    /// returns the rpn without bound variables
+    pub fn into_rpn(self) -> Rpn {
+        self.rpn
+    }
+
+    /// Replaces the rpn, keeping the options and the values of bound variables.
+    pub fn replace_rpn(&mut self, rpn: Rpn) {
+        let bound: Vec<(String, Literal)> = match &self.no_var_rpn {
+            Some(no_var_rpn) => self
+                .variables
                 .iter()
-                .map(|value| match value {
-                    Value::Variable(name) => {
-                        Ok(Value::Number(*var_map.get(name).ok_or(
-                            BindVariablesError::VariableDoesNotExist(name.to_owned()),
-                        )?))
-                    }
-                    val => Ok(val.to_owned()),
+                .filter_map(|(name, positions)| match &no_var_rpn[positions[0]] {
+                    Value::Number(num) => Some((name.to_owned(), Literal::Number(*num))),
+                    Value::Boolean(boolean) => Some((name.to_owned(), Literal::Boolean(*boolean))),
+                    _ => None,
                 })
-                .collect::<Result<Rpn, BindVariablesError>>()?,
-        );
+                .collect(),
+            None => vec![],
+        };
+
+        let options = std::mem::take(&mut self.options);
+        let functions = std::mem::take(&mut self.functions);
+        let operators = std::mem::take(&mut self.operators);
+        *self = Self::new(rpn);
+        self.options = options;
+        self.functions = functions;
+        self.operators = operators;
+        for (name, value) in bound {
+            // the variable may not be in the new rpn
+            let _ = self.set_variable(&name, value);
+        }
+    }
+
+    /// see [`EvalOptions::integer_equality`]
+    pub fn set_integer_equality(&mut self, enabled: bool) {
+        self.options.integer_equality = enabled;
+    }
+
+    /// see [`EvalOptions::coercion`]
+    pub fn set_coercion(&mut self, enabled: bool) {
+        self.options.coercion = enabled;
+    }
+
+    /// see [`EvalOptions::strict_nan`]
+    pub fn set_strict_nan(&mut self, enabled: bool) {
+        self.options.strict_nan = enabled;
+    }
+
+    /// see [`EvalOptions::angle_mode`]
+    pub fn set_angle_mode(&mut self, angle_mode: AngleMode) {
+        self.options.angle_mode = angle_mode;
+    }
+
+    /// see [`EvalOptions::unsigned_zero`]
+    pub fn set_unsigned_zero(&mut self, enabled: bool) {
+        self.options.unsigned_zero = enabled;
+    }
+
+    /// see [`EvalOptions::max_steps`]
+    pub fn set_max_steps(&mut self, max_steps: Option<usize>) {
+        self.options.max_steps = max_steps;
+    }
+
+    /// custom functions called by the rpn
+    pub fn set_functions(&mut self, functions: FunctionRegistry) {
+        self.functions = functions;
+    }
+
+    /// custom operators used by the rpn, see [`OperatorType::Custom`]
+    pub fn set_operators(&mut self, operators: OperatorTable) {
+        self.operators = operators;
+    }
+
+    /// see [`EvalOptions::operator_eval`]
+    pub fn set_operator_eval(&mut self, operator_eval: Arc<dyn OperatorEval>) {
+        self.options.operator_eval = Some(operator_eval);
+    }
+
+    /// replaces variable values with numbers
+    pub fn bind_variables(&mut self, var_map: &VariableMap) -> Result<(), BindVariablesError> {
+        let missing: Vec<String> = self
+            .variables
+            .iter()
+            .filter(|(name, _)| !var_map.contains_key(name))
+            .map(|(name, _)| name.to_owned())
+            .collect();
+        if !missing.is_empty() {
+            return Err(BindVariablesError::VariablesDoNotExist(missing));
+        }
+
+        let mut no_var_rpn = self.no_var_rpn.take().unwrap_or_else(|| self.rpn.clone());
+        for (name, positions) in &self.variables {
+            for position in positions {
+                no_var_rpn[*position] = Value::Number(var_map[name]);
+            }
+        }
+        self.no_var_rpn = Some(no_var_rpn);
+
+        Ok(())
+    }
+
+    /// names of the variables in order of their first occurrence, see [`Emitter::bind_values`]
+    pub fn variable_names(&self) -> impl Iterator<Item = &str> {
+        self.variables.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Binds the values in the order of [`Emitter::variable_names`],
+    /// without looking up the names, e.g. when binding new values in a loop.
+    pub fn bind_values(&mut self, values: &[f32]) -> Result<(), BindVariablesError> {
+        self.bind_literals(values)
+    }
+
+    /// Replaces only the occurrences of one variable, keeping the other bound values.
+    pub fn update_variable(&mut self, name: &str, value: f32) -> Result<(), BindVariablesError> {
+        self.set_variable(name, Literal::Number(value))
+    }
+
+    // same as `bind_values`, but variables can be bound to booleans as well
+    pub(crate) fn bind_literals<L>(&mut self, values: &[L]) -> Result<(), BindVariablesError>
+    where
+        L: Copy + Into<Literal>,
+    {
+        if values.len() != self.variables.len() {
+            return Err(BindVariablesError::WrongValueCount {
+                expected: self.variables.len(),
+                found: values.len(),
+            });
+        }
+
+        let no_var_rpn = self.no_var_rpn.get_or_insert_with(|| self.rpn.clone());
+        for ((_, positions), value) in self.variables.iter().zip(values) {
+            let literal: Literal = (*value).into();
+            for position in positions {
+                no_var_rpn[*position] = literal.into();
+            }
+        }
+
+        Ok(())
+    }
+
+    // replaces the occurrences of one variable with a number or a boolean
+    fn set_variable(&mut self, name: &str, value: Literal) -> Result<(), BindVariablesError> {
+        let (_, positions) = self
+            .variables
+            .iter()
+            .find(|(known, _)| known == name)
+            .ok_or_else(|| BindVariablesError::VariablesDoNotExist(vec![name.to_owned()]))?;
+
+        let no_var_rpn = self.no_var_rpn.get_or_insert_with(|| self.rpn.clone());
+        for position in positions {
+            no_var_rpn[*position] = value.into();
+        }
 
         Ok(())
     }
 
-    /// no_var_rpn cannot be empty
+    /// Fails with [`EvalError::NoVariables`] if a variable is not bound,
+    /// unless it is the left operand of `??`.
+    /// The rpn is only borrowed, so a shared emitter can be evaluated from many threads.
     pub fn eval(&self) -> Result<EmitResult, EvalError> {
-        if self.no_var_rpn.is_none() {
-            return Err(EvalError::NoVariables);
+        self.eval_into(&mut VecDeque::new())
+    }
+
+    /// Same as `eval`, but uses the given buffer as the value stack.
+    /// The buffer is cleared first, reusing it avoids an allocation per evaluation.
+    pub fn eval_into(
+        &self,
+        value_stack: &mut VecDeque<EmitResult>,
+    ) -> Result<EmitResult, EvalError> {
+        // bound variables are already replaced
+        let rpn = self.no_var_rpn.as_ref().unwrap_or(&self.rpn);
+        value_stack.clear();
+        self.eval_rpn(
+            rpn,
+            |_| Err(EvalError::NoVariables),
+            value_stack,
+            &self.options,
+            &self.functions,
+            UnboundVariables::Error,
+        )?;
+
+        Self::single_result(value_stack)
+    }
+
+    /// Evaluates the rpn with the `?` placeholders replaced by `values` in order,
+    /// variables have to be bound.
+    pub fn eval_positional(&self, values: &[f32]) -> Result<EmitResult, EvalError> {
+        if values.len() != self.placeholders.len() {
+            return Err(EvalError::PlaceholderCount {
+                expected: self.placeholders.len(),
+                found: values.len(),
+            });
         }
-        let mut rpn = self.no_var_rpn.clone().ok_or(EvalError::NoVariables)?;
 
-        // stack - 0th index in, 0th index out
+        let mut rpn = self.no_var_rpn.as_ref().unwrap_or(&self.rpn).clone();
+        for (position, value) in self.placeholders.iter().zip(values) {
+            rpn[*position] = Value::Number(*value);
+        }
+        let mut value_stack: VecDeque<EmitResult> = VecDeque::new();
+        self.eval_rpn(
+            &rpn,
+            |_| Err(EvalError::NoVariables),
+            &mut value_stack,
+            &self.options,
+            &self.functions,
+            UnboundVariables::Error,
+        )?;
+
+        Self::single_result(&mut value_stack)
+    }
+
+    /// Evaluates the rpn with variable values looked up by name instead of the bound ones.
+    /// A variable the lookup has no value for can be the left operand of `??`.
+    pub fn eval_with<F>(&self, lookup: F) -> Result<EmitResult, EvalError>
+    where
+        F: Fn(&str) -> Option<f32>,
+    {
+        let mut value_stack: VecDeque<EmitResult> = VecDeque::new();
+        self.eval_rpn(
+            &self.rpn,
+            |name| lookup(name).ok_or_else(|| EvalError::UnboundVariable(name.to_owned())),
+            &mut value_stack,
+            &self.options,
+            &self.functions,
+            UnboundVariables::Error,
+        )?;
+
+        Self::single_result(&mut value_stack)
+    }
+
+    /// Same as `eval_with`, but fails with [`EvalError::Timeout`] if the evaluation
+    /// takes longer than `timeout`, see [`EvalOptions::timeout`].
+    pub fn eval_with_timeout<F>(
+        &self,
+        lookup: F,
+        timeout: Duration,
+    ) -> Result<EmitResult, EvalError>
+    where
+        F: Fn(&str) -> Option<f32>,
+    {
+        let options = EvalOptions {
+            timeout: Some(timeout),
+            ..self.options.clone()
+        };
+        let mut value_stack: VecDeque<EmitResult> = VecDeque::new();
+        self.eval_rpn(
+            &self.rpn,
+            |name| lookup(name).ok_or_else(|| EvalError::UnboundVariable(name.to_owned())),
+            &mut value_stack,
+            &options,
+            &self.functions,
+            UnboundVariables::Error,
+        )?;
+
+        Self::single_result(&mut value_stack)
+    }
+
+    /// Evaluates the rpn with the variables, functions and options of the context,
+    /// the bound variables and the emitter options are not used.
+    pub fn eval_in(&self, ctx: &EvalContext) -> Result<EmitResult, EvalError> {
         let mut value_stack: VecDeque<EmitResult> = VecDeque::new();
+        self.eval_rpn(
+            &self.rpn,
+            |name| {
+                ctx.lookup(name)
+                    .ok_or_else(|| EvalError::UnboundVariable(name.to_owned()))
+            },
+            &mut value_stack,
+            &ctx.options,
+            &ctx.functions,
+            ctx.unbound_variables,
+        )?;
 
-        while !rpn.is_empty() {
-            let val = rpn.remove(0);
-            match val {
-                Value::Number(num) => value_stack.push_front(EmitResult::Number(num)),
-                Value::Boolean(boolean) => value_stack.push_front(EmitResult::Boolean(boolean)),
-                Value::Operator(op) => {
-                    if value_stack.len() < 2 {
-                        return Err(EvalError::NotEnoughValues);
+        Self::single_result(&mut value_stack)
+    }
+
+    fn eval_rpn<F>(
+        &self,
+        rpn: &Rpn,
+        lookup: F,
+        value_stack: &mut VecDeque<EmitResult>,
+        options: &EvalOptions,
+        functions: &FunctionRegistry,
+        unbound: UnboundVariables,
+    ) -> Result<(), EvalError>
+    where
+        F: Fn(&str) -> Result<f32, EvalError>,
+    {
+        if let Some(max) = options.max_steps {
+            if rpn.len() > max {
+                return Err(EvalError::StepLimitExceeded(max));
+            }
+        }
+
+        // `??` operators whose left operand is missing, the right operand is their result
+        let mut missing_left: Vec<usize> = vec![];
+        // the start time is only taken with a timeout, evaluation is hot
+        let deadline = options.timeout.map(|timeout| (Instant::now(), timeout));
+
+        for (index, value) in rpn.iter().enumerate() {
+            match value {
+                Value::Variable(name) => match (lookup(name), self.coalesce_targets.get(&index)) {
+                    (Ok(num), _) => value_stack.push_front(EmitResult::Number(num)),
+                    (Err(_), Some(coalesce_index)) => missing_left.push(*coalesce_index),
+                    (Err(_), None)
+                        if unbound == UnboundVariables::ZeroInArithmetic
+                            && !self.boolean_variables.contains(&index) =>
+                    {
+                        value_stack.push_front(EmitResult::Number(0.0))
                     }
-                    let second_val = value_stack.pop_front().unwrap_or_else(|| {
-                        panic!("could not pop first value of stack: {:#?}", value_stack)
-                    });
-                    let first_val = value_stack.pop_front().unwrap_or_else(|| {
-                        panic!("could not pop first value of stack: {:#?}", value_stack)
-                    });
-
-                    // unreachable because the rpn should not be created manually
-                    let val: EmitResult = match op {
-                        OperatorType::LeftParenthesis => {
-                            unreachable!("Rpn cannot have parentheses.")
-                        }
-                        OperatorType::Plus
-                        | OperatorType::Minus
-                        | OperatorType::Times
-                        | OperatorType::Divide => {
-                            let EmitResult::Number(first) = first_val else {unreachable!("value is not number, value: {first_val:#?}")};
-                            let EmitResult::Number(second) = second_val else {unreachable!("value is not number, value: {second_val:#?}")};
-                            EmitResult::Number(op.eval_nums(first, second))
-                        }
-                        OperatorType::LT
-                        | OperatorType::LE
-                        | OperatorType::GT
-                        | OperatorType::GE
-                        | OperatorType::Eq => {
-                            let EmitResult::Number(first) = first_val else {unreachable!("value is not number, value: {first_val:#?}")};
-                            let EmitResult::Number(second) = second_val else {unreachable!("value is not number, value: {second_val:#?}")};
-                            EmitResult::Boolean(op.eval_comparison(first, second))
-                        }
-                        OperatorType::And | OperatorType::Or => {
-                            let EmitResult::Boolean(first) = first_val else {unreachable!("value is not bool, value: {first_val:#?}")};
-                            let EmitResult::Boolean(second) = second_val else {unreachable!("value is not bool, value: {second_val:#?}")};
-                            EmitResult::Boolean(op.eval_conditional(first, second))
-                        }
-                    };
-                    value_stack.push_front(val);
+                    (Err(err), None) => return Err(err),
+                },
+                Value::Operator(OperatorType::Coalesce) if missing_left.last() == Some(&index) => {
+                    missing_left.pop();
                 }
-                Value::Variable(_) => {
-                    unreachable!("Rpn cannot contain variables, use `bind_variables` first")
+                _ => Self::eval_value(value, value_stack, options, functions, &self.operators)?,
+            }
+            if options.unsigned_zero {
+                Self::unsign_zero(value_stack);
+            }
+            if let Some((start, timeout)) = deadline {
+                if start.elapsed() > timeout {
+                    return Err(EvalError::Timeout(timeout));
                 }
-            };
+            }
         }
 
+        Ok(())
+    }
+
+    fn single_result(value_stack: &mut VecDeque<EmitResult>) -> Result<EmitResult, EvalError> {
         if value_stack.len() > 1 {
-            println!("{value_stack:#?}{:#?}", self.no_var_rpn);
             return Err(EvalError::TooMuchValues);
         }
 
-        Ok(value_stack[0])
+        value_stack.pop_front().ok_or(EvalError::NotEnoughValues)
+    }
+
+    /// Processes only the first `n` values of the rpn and returns the value stack,
+    /// the last element being the top of the stack.
+    pub fn eval_steps(&self, n: usize) -> Result<Vec<EmitResult>, EvalError> {
+        let rpn = self.no_var_rpn.as_ref().ok_or(EvalError::NoVariables)?;
+
+        // stack - 0th index in, 0th index out
+        let mut value_stack: VecDeque<EmitResult> = VecDeque::new();
+
+        for value in rpn.iter().take(n) {
+            Self::eval_value(
+                value,
+                &mut value_stack,
+                &self.options,
+                &self.functions,
+                &self.operators,
+            )?;
+            if self.options.unsigned_zero {
+                Self::unsign_zero(&mut value_stack);
+            }
+        }
+
+        Ok(value_stack.into_iter().rev().collect())
+    }
+
+    // replaces `-0` on top of the stack with `0`
+    fn unsign_zero(value_stack: &mut VecDeque<EmitResult>) {
+        if let Some(EmitResult::Number(num)) = value_stack.front_mut() {
+            if *num == 0.0 {
+                *num = 0.0;
+            }
+        }
+    }
+
+    /// pushes the value to the stack or applies it to the values on the stack
+    fn eval_value(
+        value: &Value,
+        value_stack: &mut VecDeque<EmitResult>,
+        options: &EvalOptions,
+        functions: &FunctionRegistry,
+        operators: &OperatorTable,
+    ) -> Result<(), EvalError> {
+        match value {
+            Value::Number(num) => value_stack.push_front(EmitResult::Number(*num)),
+            Value::Boolean(boolean) => value_stack.push_front(EmitResult::Boolean(*boolean)),
+            Value::Operator(op) if op.is_postfix() => {
+                let operand_val = value_stack.pop_front().ok_or(EvalError::NotEnoughValues)?;
+                let operand = operand_val.to_number(options.coercion).ok_or_else(|| {
+                    EvalError::TypeMismatch {
+                        operator: op.clone(),
+                        left: operand_val.result_type(),
+                        right: operand_val.result_type(),
+                    }
+                })?;
+                let result = op
+                    .eval_postfix(operand)
+                    .ok_or(EvalError::InvalidFactorial(operand))?;
+                value_stack.push_front(EmitResult::Number(result));
+            }
+            Value::Operator(op) => {
+                if value_stack.len() < 2 {
+                    return Err(EvalError::NotEnoughValues);
+                }
+                let second_val = value_stack.pop_front().unwrap_or_else(|| {
+                    panic!("could not pop first value of stack: {:#?}", value_stack)
+                });
+                let first_val = value_stack.pop_front().unwrap_or_else(|| {
+                    panic!("could not pop first value of stack: {:#?}", value_stack)
+                });
+
+                let coercion = options.coercion;
+                let operator_eval = options
+                    .operator_eval
+                    .as_deref()
+                    .unwrap_or(&DefaultOperatorEval);
+                let mismatch = || EvalError::TypeMismatch {
+                    operator: op.clone(),
+                    left: first_val.result_type(),
+                    right: second_val.result_type(),
+                };
+                let numbers = || {
+                    first_val
+                        .to_number(coercion)
+                        .zip(second_val.to_number(coercion))
+                        .ok_or_else(mismatch)
+                };
+
+                // unreachable because the rpn should not be created manually
+                let val: EmitResult = match op {
+                    OperatorType::LeftParenthesis => {
+                        unreachable!("Rpn cannot have parentheses.")
+                    }
+                    OperatorType::Factorial => unreachable!("postfix operator has one operand"),
+                    OperatorType::Coalesce => first_val,
+                    OperatorType::In => {
+                        let first = first_val.to_number(coercion).ok_or_else(mismatch)?;
+                        op.try_eval(EmitResult::Number(first), second_val)?
+                    }
+                    OperatorType::Plus
+                    | OperatorType::Minus
+                    | OperatorType::Times
+                    | OperatorType::Divide
+                    | OperatorType::BitAnd => {
+                        let (first, second) = numbers()?;
+                        EmitResult::Number(operator_eval.eval_nums(op, first, second))
+                    }
+                    OperatorType::LT
+                    | OperatorType::LE
+                    | OperatorType::GT
+                    | OperatorType::GE
+                    | OperatorType::Eq => {
+                        let (first, second) = numbers()?;
+                        if options.strict_nan && (first.is_nan() || second.is_nan()) {
+                            return Err(EvalError::NaNComparison(op.clone()));
+                        }
+                        if *op == OperatorType::Eq && options.integer_equality {
+                            EmitResult::Boolean(operator_eval.eval_comparison(
+                                op,
+                                first.round(),
+                                second.round(),
+                            ))
+                        } else {
+                            EmitResult::Boolean(operator_eval.eval_comparison(op, first, second))
+                        }
+                    }
+                    OperatorType::And | OperatorType::Or | OperatorType::Implies => {
+                        let (first, second) = first_val
+                            .to_boolean(coercion)
+                            .zip(second_val.to_boolean(coercion))
+                            .ok_or_else(mismatch)?;
+                        EmitResult::Boolean(operator_eval.eval_conditional(op, first, second))
+                    }
+                    OperatorType::Custom { symbol, .. } => {
+                        let (first, second) = numbers()?;
+                        EmitResult::Number(
+                            operators
+                                .call(symbol, first, second)
+                                .ok_or_else(|| EvalError::UnknownOperator(symbol.clone()))?,
+                        )
+                    }
+                };
+                value_stack.push_front(val);
+            }
+            // branches keep their type, so `if` cannot use `eval_nums`
+            Value::Function(FunctionType::If, _) => {
+                let else_val = value_stack.pop_front().ok_or(EvalError::NotEnoughValues)?;
+                let then_val = value_stack.pop_front().ok_or(EvalError::NotEnoughValues)?;
+                let condition_val = value_stack.pop_front().ok_or(EvalError::NotEnoughValues)?;
+
+                let condition = condition_val.to_boolean(options.coercion).ok_or_else(|| {
+                    EvalError::ArgumentTypeMismatch {
+                        function: FunctionType::If,
+                        expected: VarType::Boolean,
+                        found: condition_val.result_type(),
+                    }
+                })?;
+                value_stack.push_front(if condition { then_val } else { else_val });
+            }
+            // conditions are booleans, `eval_nums` would need them as numbers
+            Value::Function(FunctionType::FirstTrue, arg_count) => {
+                if value_stack.len() < *arg_count {
+                    return Err(EvalError::NotEnoughValues);
+                }
+                let conditions = value_stack
+                    .drain(..arg_count)
+                    .rev()
+                    .map(|arg| {
+                        arg.to_boolean(options.coercion).ok_or_else(|| {
+                            EvalError::ArgumentTypeMismatch {
+                                function: FunctionType::FirstTrue,
+                                expected: VarType::Boolean,
+                                found: arg.result_type(),
+                            }
+                        })
+                    })
+                    .collect::<Result<Vec<bool>, EvalError>>()?;
+                let index = conditions.iter().position(|condition| *condition);
+                value_stack.push_front(EmitResult::Number(
+                    index.map_or(0.0, |index| (index + 1) as f32),
+                ));
+            }
+            Value::Function(function, arg_count) => {
+                if value_stack.len() < *arg_count {
+                    return Err(EvalError::NotEnoughValues);
+                }
+                let mut args = value_stack
+                    .drain(..arg_count)
+                    .rev()
+                    .map(|arg| {
+                        arg.to_number(options.coercion).ok_or_else(|| {
+                            EvalError::ArgumentTypeMismatch {
+                                function: function.clone(),
+                                expected: VarType::Number,
+                                found: arg.result_type(),
+                            }
+                        })
+                    })
+                    .collect::<Result<Vec<f32>, EvalError>>()?;
+
+                if options.strict_nan && function.is_logarithm() {
+                    if let Some(num) = args.iter().find(|num| **num <= 0.0) {
+                        return Err(EvalError::InvalidLogarithm(*num));
+                    }
+                }
+                if function.is_trigonometric() && options.angle_mode == AngleMode::Degrees {
+                    args[0] = args[0].to_radians();
+                }
+                let result = match function {
+                    FunctionType::Custom { name, .. } => functions
+                        .call(name, &args)
+                        .ok_or_else(|| EvalError::UnknownFunction(name.to_owned()))?,
+                    _ => function.eval_nums(&args),
+                };
+                value_stack.push_front(match function.result_type() {
+                    VarType::Boolean => EmitResult::Boolean(result != 0.0),
+                    _ => EmitResult::Number(result),
+                });
+            }
+            Value::List(len) => {
+                if value_stack.len() < *len {
+                    return Err(EvalError::NotEnoughValues);
+                }
+                let nums = value_stack
+                    .drain(..len)
+                    .rev()
+                    .map(|element| {
+                        element
+                            .to_number(options.coercion)
+                            .ok_or_else(|| EvalError::InvalidListElement(element.result_type()))
+                    })
+                    .collect::<Result<Vec<f32>, EvalError>>()?;
+                value_stack.push_front(EmitResult::List(nums));
+            }
+            Value::Variable(_) => return Err(EvalError::NoVariables),
+            Value::Placeholder(index) => return Err(EvalError::UnfilledPlaceholder(*index)),
+        };
+
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{EmitResult, Emitter};
+    use super::{BindVariablesError, EmitResult, Emitter, OperatorEval};
     use crate::{
-        emitter::VariableMap,
-        parser::{OperatorType, Value},
+        emitter::{EvalError, VariableMap},
+        parser::{FunctionType, OperatorType, Value},
+        type_checker::VarType,
     };
     use pretty_assertions::assert_eq;
-    use std::collections::HashMap;
+    use std::{
+        collections::{HashMap, VecDeque},
+        sync::Arc,
+    };
 
     #[test]
     fn test_new() {
@@ -250,14 +1177,36 @@ mod tests {
     }
 
     #[test]
-    fn test_1() {
-        // 1 + 2
-        let rpn = vec![
-            Value::Number(1.0),
-            Value::Number(2.0),
+    fn test_bind_variables_missing() {
+        // z + a + z
+        let mut emitter = Emitter::new(vec![
+            Value::Variable("z".to_string()),
+            Value::Variable("a".to_string()),
             Value::Operator(OperatorType::Plus),
-        ];
-        let emitter = Emitter::new(rpn);
+            Value::Variable("z".to_string()),
+            Value::Operator(OperatorType::Plus),
+        ]);
+
+        for _ in 0..10 {
+            assert_eq!(
+                emitter.bind_variables(&HashMap::new()),
+                Err(BindVariablesError::VariablesDoNotExist(vec![
+                    "z".to_string(),
+                    "a".to_string()
+                ]))
+            );
+        }
+    }
+
+    #[test]
+    fn test_1() {
+        // 1 + 2
+        let rpn = vec![
+            Value::Number(1.0),
+            Value::Number(2.0),
+            Value::Operator(OperatorType::Plus),
+        ];
+        let emitter = Emitter::new(rpn);
 
         assert_eq!(emitter.eval().unwrap(), EmitResult::Number(3.0));
     }
@@ -380,4 +1329,588 @@ mod tests {
         let emitter = Emitter::new(rpn);
         assert_eq!(emitter.eval().unwrap(), EmitResult::Boolean(true));
     }
+
+    #[test]
+    fn test_integer_equality() {
+        // 4 / 2 = 2
+        let mut emitter = Emitter::new(vec![
+            Value::Number(4.0),
+            Value::Number(2.0),
+            Value::Operator(OperatorType::Divide),
+            Value::Number(2.0),
+            Value::Operator(OperatorType::Eq),
+        ]);
+        emitter.set_integer_equality(true);
+        assert_eq!(emitter.eval().unwrap(), EmitResult::Boolean(true));
+
+        // 5 / 2 = 2, 2.5 is rounded to 3
+        let mut emitter = Emitter::new(vec![
+            Value::Number(5.0),
+            Value::Number(2.0),
+            Value::Operator(OperatorType::Divide),
+            Value::Number(2.0),
+            Value::Operator(OperatorType::Eq),
+        ]);
+        emitter.set_integer_equality(true);
+        assert_eq!(emitter.eval().unwrap(), EmitResult::Boolean(false));
+
+        // 0.1 + 0.2 = 0.3 holds after rounding
+        let mut emitter = Emitter::new(vec![
+            Value::Number(0.1),
+            Value::Number(0.2),
+            Value::Operator(OperatorType::Plus),
+            Value::Number(0.3),
+            Value::Operator(OperatorType::Eq),
+        ]);
+        emitter.set_integer_equality(true);
+        assert_eq!(emitter.eval().unwrap(), EmitResult::Boolean(true));
+    }
+
+    #[test]
+    fn test_functions() {
+        let sign = |num: f32| {
+            Emitter::new(vec![
+                Value::Number(num),
+                Value::Function(FunctionType::Sign, 1),
+            ])
+            .eval()
+            .unwrap()
+        };
+        assert_eq!(sign(-3.0), EmitResult::Number(-1.0));
+        assert_eq!(sign(0.0), EmitResult::Number(0.0));
+        assert_eq!(sign(5.0), EmitResult::Number(1.0));
+
+        // abs(2 - 7)
+        let emitter = Emitter::new(vec![
+            Value::Number(2.0),
+            Value::Number(7.0),
+            Value::Operator(OperatorType::Minus),
+            Value::Function(FunctionType::Abs, 1),
+        ]);
+        assert_eq!(emitter.eval().unwrap(), EmitResult::Number(5.0));
+
+        let emitter = Emitter::new(vec![
+            Value::Boolean(true),
+            Value::Function(FunctionType::Abs, 1),
+        ]);
+        assert_eq!(
+            emitter.eval(),
+            Err(EvalError::ArgumentTypeMismatch {
+                function: FunctionType::Abs,
+                expected: VarType::Number,
+                found: VarType::Boolean,
+            })
+        );
+    }
+
+    #[test]
+    fn test_coercion() {
+        // (x > 0) + (y > 0)
+        let mut emitter = Emitter::new(vec![
+            Value::Variable("x".to_string()),
+            Value::Number(0.0),
+            Value::Operator(OperatorType::GT),
+            Value::Variable("y".to_string()),
+            Value::Number(0.0),
+            Value::Operator(OperatorType::GT),
+            Value::Operator(OperatorType::Plus),
+        ]);
+        emitter.set_coercion(true);
+        emitter
+            .bind_variables(&HashMap::from_iter(vec![
+                ("x".to_string(), 3.0),
+                ("y".to_string(), 5.0),
+            ]))
+            .unwrap();
+        assert_eq!(emitter.eval().unwrap(), EmitResult::Number(2.0));
+
+        // 2 && true
+        let mut emitter = Emitter::new(vec![
+            Value::Number(2.0),
+            Value::Boolean(true),
+            Value::Operator(OperatorType::And),
+        ]);
+        emitter.set_coercion(true);
+        assert_eq!(emitter.eval().unwrap(), EmitResult::Boolean(true));
+
+        // 0 || false
+        let mut emitter = Emitter::new(vec![
+            Value::Number(0.0),
+            Value::Boolean(false),
+            Value::Operator(OperatorType::Or),
+        ]);
+        emitter.set_coercion(true);
+        assert_eq!(emitter.eval().unwrap(), EmitResult::Boolean(false));
+    }
+
+    #[test]
+    fn test_eval_steps() {
+        // 1 2 3 * +
+        let emitter = Emitter::new(vec![
+            Value::Number(1.0),
+            Value::Number(2.0),
+            Value::Number(3.0),
+            Value::Operator(OperatorType::Times),
+            Value::Operator(OperatorType::Plus),
+        ]);
+
+        assert_eq!(emitter.eval_steps(0).unwrap(), vec![]);
+        assert_eq!(
+            emitter.eval_steps(3).unwrap(),
+            vec![
+                EmitResult::Number(1.0),
+                EmitResult::Number(2.0),
+                EmitResult::Number(3.0),
+            ]
+        );
+        assert_eq!(
+            emitter.eval_steps(4).unwrap(),
+            vec![EmitResult::Number(1.0), EmitResult::Number(6.0)]
+        );
+        assert_eq!(
+            emitter.eval_steps(5).unwrap(),
+            vec![EmitResult::Number(7.0)]
+        );
+        assert_eq!(
+            emitter.eval_steps(100).unwrap(),
+            vec![EmitResult::Number(7.0)]
+        );
+    }
+
+    #[test]
+    fn test_as_i64() {
+        assert_eq!(EmitResult::Number(4.0).as_i64(), Some(4));
+        assert_eq!(EmitResult::Number(-3.0).as_i64(), Some(-3));
+        assert_eq!(EmitResult::Number(2.5).as_i64(), None);
+        assert_eq!(EmitResult::Boolean(true).as_i64(), None);
+        assert_eq!(EmitResult::Number(f32::INFINITY).as_i64(), None);
+        assert_eq!(EmitResult::Number(f32::NAN).as_i64(), None);
+        assert_eq!(EmitResult::Number(1e30).as_i64(), None);
+
+        assert!(EmitResult::Number(2.0).is_integer());
+        assert!(!EmitResult::Number(2.5).is_integer());
+        assert!(!EmitResult::List(vec![1.0]).is_integer());
+    }
+
+    #[test]
+    fn test_format_sig() {
+        assert_eq!(
+            EmitResult::Number(std::f32::consts::PI).format_sig(3),
+            "3.14"
+        );
+        assert_eq!(EmitResult::Number(-2.6).format_sig(1), "-3");
+        assert_eq!(EmitResult::Number(1234.5).format_sig(6), "1234.50");
+        assert_eq!(EmitResult::Number(9.996).format_sig(3), "10.0");
+        assert_eq!(EmitResult::Number(0.0).format_sig(3), "0.00");
+        assert_eq!(EmitResult::Number(0.00123).format_sig(2), "0.0012");
+        assert_eq!(EmitResult::Number(123456789.0).format_sig(3), "1.23e8");
+        assert_eq!(EmitResult::Number(0.000012345).format_sig(3), "1.23e-5");
+        assert_eq!(EmitResult::Number(f32::INFINITY).format_sig(3), "inf");
+        assert_eq!(EmitResult::Boolean(true).format_sig(3), "true");
+    }
+
+    #[test]
+    fn test_update_variable() {
+        // x * y + x
+        let mut emitter = Emitter::new(vec![
+            Value::Variable("x".to_string()),
+            Value::Variable("y".to_string()),
+            Value::Operator(OperatorType::Times),
+            Value::Variable("x".to_string()),
+            Value::Operator(OperatorType::Plus),
+        ]);
+        emitter
+            .bind_variables(&HashMap::from_iter(vec![
+                ("x".to_string(), 1.0),
+                ("y".to_string(), 2.0),
+            ]))
+            .unwrap();
+
+        for x in 0..5 {
+            emitter.update_variable("x", x as f32).unwrap();
+            assert_eq!(
+                emitter.no_var_rpn,
+                Some(vec![
+                    Value::Number(x as f32),
+                    Value::Number(2.0),
+                    Value::Operator(OperatorType::Times),
+                    Value::Number(x as f32),
+                    Value::Operator(OperatorType::Plus),
+                ])
+            );
+            assert_eq!(emitter.eval().unwrap(), EmitResult::Number(3.0 * x as f32));
+        }
+
+        assert_eq!(
+            emitter.update_variable("z", 1.0),
+            Err(BindVariablesError::VariablesDoNotExist(vec![
+                "z".to_string()
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_update_variable_unbound() {
+        // x + y
+        let mut emitter = Emitter::new(vec![
+            Value::Variable("x".to_string()),
+            Value::Variable("y".to_string()),
+            Value::Operator(OperatorType::Plus),
+        ]);
+
+        emitter.update_variable("x", 1.0).unwrap();
+        assert_eq!(emitter.eval(), Err(EvalError::NoVariables));
+
+        emitter.update_variable("y", 2.0).unwrap();
+        assert_eq!(emitter.eval().unwrap(), EmitResult::Number(3.0));
+    }
+
+    #[test]
+    fn test_nan_comparison() {
+        // (0 / 0) = (0 / 0)
+        let rpn = vec![
+            Value::Number(0.0),
+            Value::Number(0.0),
+            Value::Operator(OperatorType::Divide),
+            Value::Number(0.0),
+            Value::Number(0.0),
+            Value::Operator(OperatorType::Divide),
+            Value::Operator(OperatorType::Eq),
+        ];
+
+        let mut emitter = Emitter::new(rpn);
+        assert_eq!(emitter.eval().unwrap(), EmitResult::Boolean(false));
+
+        emitter.set_strict_nan(true);
+        assert_eq!(
+            emitter.eval(),
+            Err(EvalError::NaNComparison(OperatorType::Eq))
+        );
+    }
+
+    #[test]
+    fn test_factorial() {
+        // (2 + 1)!
+        let emitter = Emitter::new(vec![
+            Value::Number(2.0),
+            Value::Number(1.0),
+            Value::Operator(OperatorType::Plus),
+            Value::Operator(OperatorType::Factorial),
+        ]);
+        assert_eq!(emitter.eval().unwrap(), EmitResult::Number(6.0));
+
+        let emitter = Emitter::new(vec![
+            Value::Number(-2.0),
+            Value::Operator(OperatorType::Factorial),
+        ]);
+        assert_eq!(emitter.eval(), Err(EvalError::InvalidFactorial(-2.0)));
+    }
+
+    #[test]
+    fn test_type_mismatch() {
+        // (1 > 0) + 1
+        let emitter = Emitter::new(vec![
+            Value::Number(1.0),
+            Value::Number(0.0),
+            Value::Operator(OperatorType::GT),
+            Value::Number(1.0),
+            Value::Operator(OperatorType::Plus),
+        ]);
+        let err = emitter.eval().unwrap_err();
+        assert_eq!(
+            err,
+            EvalError::TypeMismatch {
+                operator: OperatorType::Plus,
+                left: VarType::Boolean,
+                right: VarType::Number,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "operator `+` cannot be applied to boolean and number"
+        );
+
+        let emitter = Emitter::new(vec![
+            Value::Number(1.0),
+            Value::Boolean(true),
+            Value::Operator(OperatorType::And),
+        ]);
+        assert_eq!(
+            emitter.eval(),
+            Err(EvalError::TypeMismatch {
+                operator: OperatorType::And,
+                left: VarType::Number,
+                right: VarType::Boolean,
+            })
+        );
+
+        // mixed types are allowed with coercion
+        let mut emitter = Emitter::new(vec![
+            Value::Boolean(true),
+            Value::Number(1.0),
+            Value::Operator(OperatorType::Plus),
+        ]);
+        emitter.set_coercion(true);
+        assert_eq!(emitter.eval(), Ok(EmitResult::Number(2.0)));
+    }
+
+    #[test]
+    fn test_into_rpn() {
+        let rpn = vec![
+            Value::Variable("x".to_string()),
+            Value::Operator(OperatorType::Factorial),
+        ];
+        let mut emitter = Emitter::new(rpn.clone());
+        emitter
+            .bind_variables(&HashMap::from([("x".to_string(), 3.0)]))
+            .unwrap();
+        assert_eq!(emitter.into_rpn(), rpn);
+    }
+
+    #[test]
+    fn test_eval_with() {
+        // x * y + x
+        let emitter = Emitter::new(vec![
+            Value::Variable("x".to_string()),
+            Value::Variable("y".to_string()),
+            Value::Operator(OperatorType::Times),
+            Value::Variable("x".to_string()),
+            Value::Operator(OperatorType::Plus),
+        ]);
+
+        let lookup = |name: &str| match name {
+            "x" => Some(2.0),
+            "y" => Some(5.0),
+            _ => None,
+        };
+        assert_eq!(emitter.eval_with(lookup), Ok(EmitResult::Number(12.0)));
+        assert_eq!(
+            emitter.eval_with(|name| (name == "x").then_some(1.0)),
+            Err(EvalError::UnboundVariable("y".to_string()))
+        );
+        // bound values are not used
+        assert_eq!(emitter.eval(), Err(EvalError::NoVariables));
+    }
+
+    #[test]
+    fn test_eval_into() {
+        // x * 2
+        let mut emitter = Emitter::new(vec![
+            Value::Variable("x".to_string()),
+            Value::Number(2.0),
+            Value::Operator(OperatorType::Times),
+        ]);
+
+        let mut buffer = VecDeque::new();
+        for x in 0..5 {
+            emitter.update_variable("x", x as f32).unwrap();
+            assert_eq!(
+                emitter.eval_into(&mut buffer),
+                Ok(EmitResult::Number(x as f32 * 2.0))
+            );
+        }
+
+        // leftovers of a failed evaluation do not leak into the next one
+        let failing = Emitter::new(vec![Value::Number(1.0), Value::Number(2.0)]);
+        assert_eq!(
+            failing.eval_into(&mut buffer),
+            Err(EvalError::TooMuchValues)
+        );
+        assert_eq!(emitter.eval_into(&mut buffer), Ok(EmitResult::Number(8.0)));
+    }
+
+    #[test]
+    fn test_if_function() {
+        // if(x > 0, true, false)
+        let mut emitter = Emitter::new(vec![
+            Value::Variable("x".to_string()),
+            Value::Number(0.0),
+            Value::Operator(OperatorType::GT),
+            Value::Boolean(true),
+            Value::Boolean(false),
+            Value::Function(FunctionType::If, 3),
+        ]);
+        emitter.update_variable("x", 1.0).unwrap();
+        assert_eq!(emitter.eval(), Ok(EmitResult::Boolean(true)));
+        emitter.update_variable("x", -1.0).unwrap();
+        assert_eq!(emitter.eval(), Ok(EmitResult::Boolean(false)));
+
+        // if(1, 2, 3)
+        let mut emitter = Emitter::new(vec![
+            Value::Number(1.0),
+            Value::Number(2.0),
+            Value::Number(3.0),
+            Value::Function(FunctionType::If, 3),
+        ]);
+        assert_eq!(
+            emitter.eval(),
+            Err(EvalError::ArgumentTypeMismatch {
+                function: FunctionType::If,
+                expected: VarType::Boolean,
+                found: VarType::Number,
+            })
+        );
+        emitter.set_coercion(true);
+        assert_eq!(emitter.eval(), Ok(EmitResult::Number(2.0)));
+    }
+
+    #[derive(Debug)]
+    struct SaturatingEval;
+
+    impl OperatorEval for SaturatingEval {
+        fn eval_nums(&self, op: &OperatorType, first: f32, second: f32) -> f32 {
+            op.eval_nums(first, second).clamp(f32::MIN, f32::MAX)
+        }
+    }
+
+    #[test]
+    fn test_operator_eval() {
+        // x + y
+        let mut emitter = Emitter::new(vec![
+            Value::Variable("x".to_string()),
+            Value::Variable("y".to_string()),
+            Value::Operator(OperatorType::Plus),
+        ]);
+        emitter
+            .bind_variables(&HashMap::from([
+                ("x".to_string(), f32::MAX),
+                ("y".to_string(), f32::MAX),
+            ]))
+            .unwrap();
+        assert_eq!(emitter.eval(), Ok(EmitResult::Number(f32::INFINITY)));
+
+        emitter.set_operator_eval(Arc::new(SaturatingEval));
+        assert_eq!(emitter.eval(), Ok(EmitResult::Number(f32::MAX)));
+        emitter.update_variable("y", 1.0).unwrap();
+        assert_eq!(emitter.eval(), Ok(EmitResult::Number(f32::MAX)));
+        emitter.update_variable("x", 1.0).unwrap();
+        assert_eq!(emitter.eval(), Ok(EmitResult::Number(2.0)));
+
+        // methods which are not overridden keep the default evaluation, x > y
+        let mut emitter = Emitter::new(vec![
+            Value::Variable("x".to_string()),
+            Value::Variable("y".to_string()),
+            Value::Operator(OperatorType::GT),
+        ]);
+        emitter.set_operator_eval(Arc::new(SaturatingEval));
+        emitter
+            .bind_variables(&HashMap::from([
+                ("x".to_string(), 2.0),
+                ("y".to_string(), 1.0),
+            ]))
+            .unwrap();
+        assert_eq!(emitter.eval(), Ok(EmitResult::Boolean(true)));
+        emitter.update_variable("x", f32::NAN).unwrap();
+        assert_eq!(emitter.eval(), Ok(EmitResult::Boolean(false)));
+    }
+
+    #[test]
+    fn test_replace_rpn() {
+        // x - y
+        let mut emitter = Emitter::new(vec![
+            Value::Variable("x".to_string()),
+            Value::Variable("y".to_string()),
+            Value::Operator(OperatorType::Minus),
+        ]);
+        emitter.set_integer_equality(true);
+        emitter
+            .bind_variables(&HashMap::from([
+                ("x".to_string(), 5.0),
+                ("y".to_string(), 2.0),
+            ]))
+            .unwrap();
+
+        // y - x = -3.4
+        emitter.replace_rpn(vec![
+            Value::Variable("y".to_string()),
+            Value::Variable("x".to_string()),
+            Value::Operator(OperatorType::Minus),
+            Value::Number(-3.4),
+            Value::Operator(OperatorType::Eq),
+        ]);
+        assert_eq!(emitter.eval(), Ok(EmitResult::Boolean(true)));
+    }
+
+    #[test]
+    fn test_coalesce() {
+        // x ?? (y ?? 5)
+        let mut emitter = Emitter::new(vec![
+            Value::Variable("x".to_string()),
+            Value::Variable("y".to_string()),
+            Value::Number(5.0),
+            Value::Operator(OperatorType::Coalesce),
+            Value::Operator(OperatorType::Coalesce),
+        ]);
+
+        assert_eq!(emitter.eval(), Ok(EmitResult::Number(5.0)));
+        assert_eq!(
+            emitter.eval_with(|name| (name == "y").then_some(2.0)),
+            Ok(EmitResult::Number(2.0))
+        );
+        emitter.update_variable("x", 1.0).unwrap();
+        assert_eq!(emitter.eval(), Ok(EmitResult::Number(1.0)));
+
+        // (x + 1) ?? 5, the left operand is not a single variable
+        let emitter = Emitter::new(vec![
+            Value::Variable("x".to_string()),
+            Value::Number(1.0),
+            Value::Operator(OperatorType::Plus),
+            Value::Number(5.0),
+            Value::Operator(OperatorType::Coalesce),
+        ]);
+        assert_eq!(
+            emitter.eval_with(|_| None),
+            Err(EvalError::UnboundVariable("x".to_string()))
+        );
+        assert_eq!(
+            emitter.eval_with(|_| Some(1.0)),
+            Ok(EmitResult::Number(2.0))
+        );
+    }
+
+    #[test]
+    fn test_shared_eval() {
+        // x * 2 > 3
+        let mut emitter = Emitter::new(vec![
+            Value::Variable("x".to_string()),
+            Value::Number(2.0),
+            Value::Operator(OperatorType::Times),
+            Value::Number(3.0),
+            Value::Operator(OperatorType::GT),
+        ]);
+        emitter.update_variable("x", 2.0).unwrap();
+
+        let emitter = Arc::new(emitter);
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let emitter = Arc::clone(&emitter);
+                std::thread::spawn(move || emitter.eval())
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), Ok(EmitResult::Boolean(true)));
+        }
+    }
+
+    #[test]
+    fn test_list() {
+        // (1, 2 > 1, x)
+        let mut emitter = Emitter::new(vec![
+            Value::Number(1.0),
+            Value::Number(2.0),
+            Value::Number(1.0),
+            Value::Operator(OperatorType::GT),
+            Value::Variable("x".to_string()),
+            Value::List(3),
+        ]);
+        emitter.update_variable("x", 3.0).unwrap();
+        assert_eq!(
+            emitter.eval(),
+            Err(EvalError::InvalidListElement(VarType::Boolean))
+        );
+        emitter.set_coercion(true);
+        assert_eq!(emitter.eval(), Ok(EmitResult::List(vec![1.0, 1.0, 3.0])));
+
+        assert_eq!(EmitResult::List(vec![1.0, 2.5]).format_sig(2), "[1.0, 2.5]");
+    }
 }
@@ -1,9 +1,22 @@
 use crate::{
-    emitter::{BindVariablesError, EmitResult, Emitter, VariableMap},
-    parser::tokens_to_rpn,
-    tokenizer::Tokenizer,
+    ast::{Ast, DerivativeError},
+    context::{EvalContext, FunctionRegistry, OperatorTable, VariableResolver},
+    emitter::{
+        boolean_variables, AngleMode, BindVariablesError, EmitResult, Emitter, OperatorEval,
+        VariableMap,
+    },
+    parser::{OperatorType, Parser, Rpn, Value},
+    tokenizer::{Span, Token, TokenList, TokenType, Tokenizer},
+    type_checker::{check_types, ResultType, TypeError, VarType},
     EvalError, ParserError, TokenizerError,
 };
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet, VecDeque},
+    marker::PhantomData,
+    sync::Arc,
+    time::Duration,
+};
 
 #[derive(Debug, Clone)]
 pub struct Expression {
@@ -15,6 +28,18 @@ pub struct Expression {
 pub enum ExpressionCreationError {
     TokenizerError(TokenizerError),
     ParserError(ParserError),
+    // name of a called function which is neither built-in nor registered
+    UnknownFunction(String),
+    // the parsed rpn does not reduce to a single value, e.g. for an empty expression
+    InvalidStructure,
+    // a part without variables evaluates to `NaN` or an infinity, e.g. `0/0`
+    AlwaysNaN,
+    // an operand of `=` contains a division, see `ExpressionBuilder::reject_fragile_comparisons`
+    FragileComparison,
+    // operator missing from the allowed operators of the builder
+    OperatorNotAllowed(OperatorType),
+    TooLong { max: usize, found: usize },
+    TooManyTokens { max: usize, found: usize },
 }
 
 impl std::fmt::Display for ExpressionCreationError {
@@ -22,6 +47,46 @@ impl std::fmt::Display for ExpressionCreationError {
         match self {
             ExpressionCreationError::TokenizerError(err) => err.fmt(f),
             ExpressionCreationError::ParserError(err) => err.fmt(f),
+            ExpressionCreationError::UnknownFunction(name) => {
+                write!(f, "unknown function: '{name}'")
+            }
+            ExpressionCreationError::InvalidStructure => {
+                write!(f, "expression does not evaluate to a single value")
+            }
+            ExpressionCreationError::AlwaysNaN => {
+                write!(
+                    f,
+                    "expression contains a constant evaluating to NaN or infinity"
+                )
+            }
+            ExpressionCreationError::FragileComparison => {
+                write!(
+                    f,
+                    "`=` compares the result of a division, which may be rounded"
+                )
+            }
+            ExpressionCreationError::OperatorNotAllowed(op) => {
+                write!(f, "operator `{op}` is not allowed")
+            }
+            ExpressionCreationError::TooLong { max, found } => write!(
+                f,
+                "expression is {found} bytes long, at most {max} are allowed"
+            ),
+            ExpressionCreationError::TooManyTokens { max, found } => write!(
+                f,
+                "expression has {found} tokens, at most {max} are allowed"
+            ),
+        }
+    }
+}
+
+impl ExpressionCreationError {
+    /// Renders the error with the `source` expression, tokenizer errors underline
+    /// the unmatched character, see [`TokenizerError::reported`].
+    pub fn reported(&self, source: &str) -> String {
+        match self {
+            ExpressionCreationError::TokenizerError(err) => err.reported(source),
+            err => format!("error: {err}\n  |\n  | {source}"),
         }
     }
 }
@@ -34,23 +99,449 @@ impl From<TokenizerError> for ExpressionCreationError {
 
 impl From<ParserError> for ExpressionCreationError {
     fn from(value: ParserError) -> Self {
-        Self::ParserError(value)
+        match value {
+            ParserError::UnknownFunction(name) => Self::UnknownFunction(name),
+            err => Self::ParserError(err),
+        }
     }
 }
 
-impl Expression {
-    /// Creates and expression from standard infix string.
-    pub fn new(expr: &str) -> Result<Expression, ExpressionCreationError> {
-        let tokenizer = Tokenizer::new();
+/// Error of [`Expression::eval_with_expr_vars`].
+#[derive(Debug)]
+pub enum ExprVarsError {
+    // name of the variable whose definition failed to parse, and the error
+    InvalidDefinition(String, ExpressionCreationError),
+    // variables of the cycle in the order they depend on each other,
+    // the first one is repeated at the end
+    Cycle(Vec<String>),
+    Eval(EvalError),
+}
+
+impl std::fmt::Display for ExprVarsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExprVarsError::InvalidDefinition(name, err) => {
+                write!(f, "invalid definition of variable '{name}': {err}")
+            }
+            ExprVarsError::Cycle(names) => {
+                write!(f, "variables depend on themselves: {}", names.join(" -> "))
+            }
+            ExprVarsError::Eval(err) => err.fmt(f),
+        }
+    }
+}
+
+impl From<EvalError> for ExprVarsError {
+    fn from(value: EvalError) -> Self {
+        Self::Eval(value)
+    }
+}
+
+/// Limits on the size of parsed expressions, `None` means unlimited.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    /// maximum length of the expression string in bytes
+    pub max_length: Option<usize>,
+    pub max_tokens: Option<usize>,
+}
+
+/// Creates expressions with non-default options, see [`Expression::builder`].
+#[derive(Debug, Clone, Default)]
+pub struct ExpressionBuilder {
+    constants: HashMap<String, f32>,
+    macros: HashMap<String, Rpn>,
+    limits: Limits,
+    variable_delimiters: Option<(String, String)>,
+    decimal_comma: bool,
+    implicit_multiplication: bool,
+    scientific_constants: bool,
+    reject_non_finite: bool,
+    reject_fragile_comparisons: bool,
+    // `None` allows every operator
+    allowed_operators: Option<HashSet<OperatorType>>,
+    functions: FunctionRegistry,
+    operators: OperatorTable,
+}
+
+impl ExpressionBuilder {
+    /// Named constants usable without braces, e.g. `2 * pi`.
+    pub fn constants(mut self, constants: HashMap<String, f32>) -> Self {
+        self.constants = constants;
+        self
+    }
+
+    /// Parses `expr` as a named snippet usable by later expressions and macros,
+    /// e.g. `dist` for `sqrt({x}*{x} + {y}*{y})` in `dist > 5`.
+    pub fn define_macro(mut self, name: &str, expr: &str) -> Result<Self, ExpressionCreationError> {
+        let rpn = self.build(expr)?.into_rpn();
+        self.macros.insert(name.to_owned(), rpn);
+        Ok(self)
+    }
+
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Replaces the default `{` and `}` around variable names.
+    pub fn variable_delimiters(mut self, open: &str, close: &str) -> Self {
+        self.variable_delimiters = Some((open.to_owned(), close.to_owned()));
+        self
+    }
+
+    /// Custom functions callable by the expressions, in addition to the built-in ones.
+    pub fn functions(mut self, functions: FunctionRegistry) -> Self {
+        self.functions = functions;
+        self
+    }
+
+    /// Custom binary operators usable by the expressions, in addition to the built-in ones,
+    /// see [`OperatorTable::register`].
+    pub fn operators(mut self, operators: OperatorTable) -> Self {
+        self.operators = operators;
+        self
+    }
+
+    /// Uses `,` as the decimal separator, see [`Tokenizer::with_decimal_comma`].
+    pub fn decimal_comma(mut self, enabled: bool) -> Self {
+        self.decimal_comma = enabled;
+        self
+    }
+
+    /// Fails with [`ExpressionCreationError::AlwaysNaN`] if a part of the expression without
+    /// variables evaluates to `NaN` or an infinity, e.g. `0/0`, `sqrt(0 - 1)` or `log(0)`.
+    pub fn reject_non_finite(mut self, enabled: bool) -> Self {
+        self.reject_non_finite = enabled;
+        self
+    }
+
+    /// Fails with [`ExpressionCreationError::FragileComparison`] if an operand of `=`
+    /// contains a division, e.g. `1/3 = 0.333`, see [`Ast::has_fragile_comparison`].
+    pub fn reject_fragile_comparisons(mut self, enabled: bool) -> Self {
+        self.reject_fragile_comparisons = enabled;
+        self
+    }
+
+    /// Resolves `pi`, `e`, `tau`, `phi`, `sqrt2` and `inf` unless they are defined by `constants`,
+    /// see [`Parser::with_scientific_constants`].
+    pub fn scientific_constants(mut self, enabled: bool) -> Self {
+        self.scientific_constants = enabled;
+        self
+    }
+
+    /// Accepts `2{x}` as `2 * {x}`, see [`Parser::with_implicit_multiplication`].
+    pub fn implicit_multiplication(mut self, enabled: bool) -> Self {
+        self.implicit_multiplication = enabled;
+        self
+    }
+
+    /// Rejects expressions using other operators with
+    /// [`ExpressionCreationError::OperatorNotAllowed`], e.g. [`OperatorType::ARITHMETIC`]
+    /// for a calculator without comparisons and logical operators.
+    pub fn allowed_operators(mut self, operators: HashSet<OperatorType>) -> Self {
+        self.allowed_operators = Some(operators);
+        self
+    }
+
+    fn tokenizer(&self) -> Tokenizer {
+        let tokenizer = match &self.variable_delimiters {
+            Some((open, close)) => Tokenizer::new().with_variable_delimiters(open, close),
+            None => Tokenizer::new(),
+        }
+        .with_operator_symbols(self.operators.symbols());
+        if self.decimal_comma {
+            tokenizer.with_decimal_comma()
+        } else {
+            tokenizer
+        }
+    }
+
+    /// Creates an expression from standard infix string using the builder options.
+    pub fn build(&self, expr: &str) -> Result<Expression, ExpressionCreationError> {
+        self.build_with(&self.tokenizer(), expr)
+    }
+
+    /// Creates an expression from each string, sharing one tokenizer between them.
+    pub fn build_many(&self, exprs: &[&str]) -> Vec<Result<Expression, ExpressionCreationError>> {
+        let tokenizer = self.tokenizer();
+        exprs
+            .iter()
+            .map(|expr| self.build_with(&tokenizer, expr))
+            .collect()
+    }
+
+    /// Returns the tokens and the reverse polish notation the expression is built from,
+    /// limits are not checked.
+    pub fn debug_pipeline(&self, expr: &str) -> Result<(TokenList, Rpn), ExpressionCreationError> {
+        let tokens = self.tokenizer().tokenize(expr)?;
+        let rpn = self.parser().parse(tokens.clone())?;
+        Ok((tokens, rpn))
+    }
+
+    fn parser(&self) -> Parser {
+        Parser::new()
+            .with_constants(self.constants.clone())
+            .with_macros(self.macros.clone())
+            .with_functions(self.functions.clone())
+            .with_operators(self.operators.clone())
+            .with_implicit_multiplication(self.implicit_multiplication)
+            .with_scientific_constants(self.scientific_constants)
+    }
+
+    fn build_with(
+        &self,
+        tokenizer: &Tokenizer,
+        expr: &str,
+    ) -> Result<Expression, ExpressionCreationError> {
+        if let Some(max) = self.limits.max_length {
+            if expr.len() > max {
+                return Err(ExpressionCreationError::TooLong {
+                    max,
+                    found: expr.len(),
+                });
+            }
+        }
+
         let tokens = tokenizer.tokenize(expr)?;
-        let rpn = tokens_to_rpn(tokens)?;
-        let emitter = Emitter::new(rpn);
+        if let Some(max) = self.limits.max_tokens {
+            if tokens.len() > max {
+                return Err(ExpressionCreationError::TooManyTokens {
+                    max,
+                    found: tokens.len(),
+                });
+            }
+        }
 
-        Ok(Self {
+        let rpn = self.parser().parse(tokens)?;
+        if !reduces_to_single_value(&rpn) {
+            return Err(ExpressionCreationError::InvalidStructure);
+        }
+        if let Some(allowed) = &self.allowed_operators {
+            let not_allowed = rpn.iter().find_map(|value| match value {
+                Value::Operator(op) if !allowed.contains(op) => Some(op.clone()),
+                _ => None,
+            });
+            if let Some(op) = not_allowed {
+                return Err(ExpressionCreationError::OperatorNotAllowed(op));
+            }
+        }
+        if self.reject_non_finite
+            && Ast::from_rpn(&rpn).is_some_and(|ast| ast.has_non_finite_constant())
+        {
+            return Err(ExpressionCreationError::AlwaysNaN);
+        }
+        if self.reject_fragile_comparisons
+            && Ast::from_rpn(&rpn).is_some_and(|ast| ast.has_fragile_comparison())
+        {
+            return Err(ExpressionCreationError::FragileComparison);
+        }
+        let mut emitter = Emitter::new(rpn);
+        emitter.set_functions(self.functions.clone());
+        emitter.set_operators(self.operators.clone());
+
+        Ok(Expression {
             str_expr: expr.to_owned(),
             emitter,
         })
     }
+}
+
+/// Checks that evaluating the rpn never runs out of values and leaves exactly one,
+/// so that evaluation cannot fail with `NotEnoughValues` or `TooMuchValues`.
+fn reduces_to_single_value(rpn: &Rpn) -> bool {
+    let mut depth: usize = 0;
+    for value in rpn {
+        let consumed = match value {
+            Value::Number(_) | Value::Boolean(_) | Value::Variable(_) | Value::Placeholder(_) => 0,
+            Value::Operator(op) if op.is_postfix() => 1,
+            Value::Operator(_) => 2,
+            Value::Function(_, arg_count) | Value::List(arg_count) => *arg_count,
+        };
+        match depth.checked_sub(consumed) {
+            Some(remaining) => depth = remaining + 1,
+            None => return false,
+        }
+    }
+
+    depth == 1
+}
+
+// evaluates the definition of `name` after the variables it uses into `values`,
+// `path` are the variables being resolved which depend on `name`
+fn resolve_expr_var(
+    name: &str,
+    defs: &HashMap<String, String>,
+    values: &mut VariableMap,
+    path: &mut Vec<String>,
+) -> Result<(), ExprVarsError> {
+    let Some(def) = defs.get(name) else {
+        // left unbound, e.g. for `??`
+        return Ok(());
+    };
+    if values.contains_key(name) {
+        return Ok(());
+    }
+    if let Some(start) = path.iter().position(|resolving| resolving == name) {
+        let mut cycle = path.split_off(start);
+        cycle.push(name.to_owned());
+        return Err(ExprVarsError::Cycle(cycle));
+    }
+
+    let expr = Expression::new(def)
+        .map_err(|err| ExprVarsError::InvalidDefinition(name.to_owned(), err))?;
+    path.push(name.to_owned());
+    for used in expr.variable_names() {
+        resolve_expr_var(used, defs, values, path)?;
+    }
+    path.pop();
+
+    match expr.eval_with(|used| values.get(used).copied())? {
+        EmitResult::Number(num) => values.insert(name.to_owned(), num),
+        result => {
+            return Err(ExprVarsError::Eval(EvalError::WrongResultType {
+                expected: VarType::Number,
+                found: result.result_type(),
+            }))
+        }
+    };
+
+    Ok(())
+}
+
+// indices of the matching `(` and `)` tokens, in order of the `(`
+fn paren_pairs(tokens: &[(Token, Span)]) -> Vec<(usize, usize)> {
+    let mut open: Vec<usize> = vec![];
+    let mut pairs = vec![];
+    for (index, (token, _)) in tokens.iter().enumerate() {
+        if is_paren(token, "(") {
+            open.push(index);
+        } else if is_paren(token, ")") {
+            if let Some(start) = open.pop() {
+                pairs.push((start, index));
+            }
+        }
+    }
+    pairs.sort();
+    pairs
+}
+
+fn is_call_name(token: &Token) -> bool {
+    matches!(
+        token.token_type,
+        TokenType::Identifier | TokenType::Function
+    )
+}
+
+fn is_paren(token: &Token, paren: &str) -> bool {
+    token.token_type == TokenType::Parenthesis && token.value == paren
+}
+
+fn operator_priority(token: &Token) -> u32 {
+    OperatorType::from_str(&token.value)
+        .expect("default tokenizer only produces builtin operators")
+        .priority()
+        .expect("operator token should not be a parenthesis")
+}
+
+// whether removing the parentheses from `open` to `close` keeps the parse
+fn is_redundant_group(
+    tokens: &[(Token, Span)],
+    pairs: &[(usize, usize)],
+    open: usize,
+    close: usize,
+) -> bool {
+    if open + 1 == close {
+        return false;
+    }
+    // lowest priority of the operators outside of nested parentheses, `None` without operators
+    let mut lowest: Option<u32> = None;
+    let mut depth = 0;
+    for (token, _) in &tokens[open + 1..close] {
+        if is_paren(token, "(") {
+            depth += 1;
+        } else if is_paren(token, ")") {
+            depth -= 1;
+        } else if depth == 0 && token.token_type == TokenType::Comma {
+            // a list
+            return false;
+        } else if depth == 0 && token.token_type == TokenType::Operator {
+            let priority = operator_priority(token);
+            lowest = Some(lowest.map_or(priority, |lowest| lowest.min(priority)));
+        }
+    }
+
+    // parentheses directly enclosing the group do not separate it from the operators around
+    let (mut open, mut close) = (open, close);
+    while open > 0
+        && pairs.contains(&(open - 1, close + 1))
+        && !(open > 1 && is_call_name(&tokens[open - 2].0))
+    {
+        (open, close) = (open - 1, close + 1);
+    }
+
+    let keeps_parse = |neighbor: Option<&(Token, Span)>, is_left: bool| match neighbor {
+        None => true,
+        Some((token, _)) if token.token_type == TokenType::Operator => {
+            let outside = operator_priority(token);
+            // operators are left-associative, `1 - (2 - 3)` differs from `1 - 2 - 3`
+            match lowest {
+                None => true,
+                Some(lowest) if is_left => lowest > outside,
+                Some(lowest) => lowest >= outside,
+            }
+        }
+        Some((token, _)) => {
+            token.token_type == TokenType::Comma || is_paren(token, if is_left { "(" } else { ")" })
+        }
+    };
+
+    keeps_parse(open.checked_sub(1).map(|index| &tokens[index]), true)
+        && keeps_parse(tokens.get(close + 1), false)
+}
+
+impl Expression {
+    /// Creates and expression from standard infix string.
+    pub fn new(expr: &str) -> Result<Expression, ExpressionCreationError> {
+        Self::builder().build(expr)
+    }
+
+    /// Creates an expression from each string, the tokenizer is created only once.
+    pub fn new_many(exprs: &[&str]) -> Vec<Result<Expression, ExpressionCreationError>> {
+        Self::builder().build_many(exprs)
+    }
+
+    /// Returns the intermediate tokens and reverse polish notation of the expression.
+    pub fn debug_pipeline(expr: &str) -> Result<(TokenList, Rpn), ExpressionCreationError> {
+        Self::builder().debug_pipeline(expr)
+    }
+
+    /// Spans of parentheses which do not change how `source` is parsed, from the `(`
+    /// to the `)`, e.g. in `1 + (2 * 3)` but not in `(1 + 2) * 3`.
+    /// Parentheses of function calls and lists are not reported.
+    /// Returns no spans if `source` cannot be tokenized.
+    pub fn redundant_parens(source: &str) -> Vec<Span> {
+        let Ok(tokens) = Tokenizer::new().tokenize_spanned(source) else {
+            return vec![];
+        };
+        let pairs = paren_pairs(&tokens);
+
+        pairs
+            .iter()
+            .filter(|(open, close)| {
+                let is_call = *open > 0 && is_call_name(&tokens[open - 1].0);
+                !is_call && is_redundant_group(&tokens, &pairs, *open, *close)
+            })
+            .map(|(open, close)| Span {
+                start: tokens[*open].1.start,
+                end: tokens[*close].1.end,
+            })
+            .collect()
+    }
+
+    /// Returns a builder for creating expressions with constants, limits and other options.
+    pub fn builder() -> ExpressionBuilder {
+        ExpressionBuilder::default()
+    }
 
     /// Used to bind variables to numbers.
     /// Takes a hashmap as an argument where the keys are the variable names and the values are f32
@@ -59,96 +550,2380 @@ impl Expression {
         self.emitter.bind_variables(var_map)
     }
 
+    /// Returns a copy of the expression with the variables bound, the original stays untouched.
+    pub fn with_bindings(&self, var_map: &VariableMap) -> Result<Expression, BindVariablesError> {
+        let mut expr = self.clone();
+        expr.bind_variables(var_map)?;
+        Ok(expr)
+    }
+
+    /// Binds a single variable, keeping the values of the other variables.
+    /// Cheaper than `bind_variables` when only one variable changes between evaluations.
+    pub fn update_variable(&mut self, name: &str, value: f32) -> Result<(), BindVariablesError> {
+        self.emitter.update_variable(name, value)
+    }
+
+    /// names of the variables in order of their first occurrence, see [`Expression::bind_values`]
+    pub fn variable_names(&self) -> Vec<&str> {
+        self.emitter.variable_names().collect()
+    }
+
+    /// Binds the variables to the fields of a JSON object with the same names,
+    /// numbers are bound as `f32` and booleans as booleans, e.g. for `{enabled} && {x} > 1`.
+    /// Fields which are not variables are ignored, a variable whose field is
+    /// neither a number nor a boolean fails with [`BindVariablesError::UnsupportedJsonValue`].
+    #[cfg(feature = "json")]
+    pub fn bind_json(&mut self, obj: &serde_json::Value) -> Result<(), BindVariablesError> {
+        use crate::emitter::Literal;
+
+        let fields = obj.as_object().ok_or(BindVariablesError::NotAnObject)?;
+        let mut values = vec![];
+        let mut missing = vec![];
+        for name in self.variable_names() {
+            let value = match fields.get(name) {
+                Some(serde_json::Value::Number(num)) => {
+                    num.as_f64().map(|num| Literal::Number(num as f32))
+                }
+                Some(serde_json::Value::Bool(boolean)) => Some(Literal::Boolean(*boolean)),
+                Some(_) => None,
+                None => {
+                    missing.push(name.to_owned());
+                    continue;
+                }
+            };
+            values.push(
+                value.ok_or_else(|| BindVariablesError::UnsupportedJsonValue(name.to_owned()))?,
+            );
+        }
+        if !missing.is_empty() {
+            return Err(BindVariablesError::VariablesDoNotExist(missing));
+        }
+
+        self.emitter.bind_literals(&values)
+    }
+
+    /// Binds the values in the order of [`Expression::variable_names`].
+    /// Cheaper than `bind_variables` as no names are looked up.
+    pub fn bind_values(&mut self, values: &[f32]) -> Result<(), BindVariablesError> {
+        self.emitter.bind_values(values)
+    }
+
+    /// Rounds both operands of `=` to the nearest integer before comparing them.
+    /// Halfway values are rounded away from zero, so `2.5 = 2` is false.
+    pub fn set_integer_equality(&mut self, enabled: bool) {
+        self.emitter.set_integer_equality(enabled);
+    }
+
+    /// Converts booleans to `1` or `0` in arithmetic and nonzero numbers to `true`
+    /// in logical operators instead of failing on mismatched types.
+    pub fn set_coercion(&mut self, enabled: bool) {
+        self.emitter.set_coercion(enabled);
+    }
+
+    /// Checks that the expression references only the `allowed` variables.
+    /// Returns the other referenced variable names in order of their first occurrence.
+    pub fn validate_variables(&self, allowed: &HashSet<String>) -> Result<(), Vec<String>> {
+        let mut disallowed: Vec<String> = vec![];
+        for value in self.emitter.rpn() {
+            if let Value::Variable(name) = value {
+                if !allowed.contains(name) && !disallowed.contains(name) {
+                    disallowed.push(name.to_owned());
+                }
+            }
+        }
+
+        if disallowed.is_empty() {
+            Ok(())
+        } else {
+            Err(disallowed)
+        }
+    }
+
+    /// Checks that none of the number variables of `vars` is used as a condition,
+    /// e.g. as an operand of `&&` or the condition of `if`, before binding them.
+    /// Returns the misused variable names in order of their first occurrence.
+    pub fn check_binding(&self, vars: &VariableMap) -> Result<(), Vec<String>> {
+        let rpn = self.emitter.rpn();
+        let mut boolean_variables: Vec<usize> = boolean_variables(rpn).into_iter().collect();
+        boolean_variables.sort();
+
+        let mut misused: Vec<String> = vec![];
+        for index in boolean_variables {
+            if let Value::Variable(name) = &rpn[index] {
+                if vars.contains_key(name) && !misused.contains(name) {
+                    misused.push(name.to_owned());
+                }
+            }
+        }
+
+        if misused.is_empty() {
+            Ok(())
+        } else {
+            Err(misused)
+        }
+    }
+
+    /// Returns the variables missing from `partial` which can still change the result,
+    /// taking short-circuiting of `&&`, `||` and `if` into account.
+    /// E.g. in `{x} > 0 && {y} > 0`, `{y}` does not matter when `x` is negative.
+    pub fn effective_variables(&self, partial: &VariableMap) -> Vec<String> {
+        self.ast().effective_variables(partial)
+    }
+
+    /// Returns how many times each variable is referenced in the expression.
+    pub fn variable_counts(&self) -> HashMap<String, usize> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for value in self.emitter.rpn() {
+            if let Value::Variable(name) = value {
+                *counts.entry(name.to_owned()).or_default() += 1;
+            }
+        }
+
+        counts
+    }
+
+    /// Returns every number literal in order of appearance, e.g. for flagging magic numbers.
+    /// Named constants were replaced by their values during parsing and are included,
+    /// values of bound variables are not.
+    pub fn literals(&self) -> Vec<f32> {
+        self.emitter
+            .rpn()
+            .iter()
+            .filter_map(|value| match value {
+                Value::Number(num) => Some(*num),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// By default, comparisons with `NaN` (e.g. from `0/0`) are false, including `NaN = NaN`.
+    /// In strict mode they fail with [`EvalError::NaNComparison`] instead,
+    /// and logarithms of non-positive numbers fail with [`EvalError::InvalidLogarithm`].
+    pub fn set_strict_nan(&mut self, enabled: bool) {
+        self.emitter.set_strict_nan(enabled);
+    }
+
+    /// Sets whether `sin`, `cos` and `tan` take radians or degrees.
+    pub fn set_angle_mode(&mut self, angle_mode: AngleMode) {
+        self.emitter.set_angle_mode(angle_mode);
+    }
+
+    /// Replaces `-0` with `0` after every step, see [`crate::EvalOptions::unsigned_zero`].
+    pub fn set_unsigned_zero(&mut self, enabled: bool) {
+        self.emitter.set_unsigned_zero(enabled);
+    }
+
+    /// Limits the number of evaluation steps, see [`crate::EvalOptions::max_steps`].
+    /// Protects against huge expressions, e.g. from expanded macros.
+    pub fn set_max_steps(&mut self, max_steps: Option<usize>) {
+        self.emitter.set_max_steps(max_steps);
+    }
+
+    /// Replaces the built-in evaluation of binary operators, see [`OperatorEval`].
+    pub fn set_operator_eval(&mut self, operator_eval: Arc<dyn OperatorEval>) {
+        self.emitter.set_operator_eval(operator_eval);
+    }
+
+    /// Returns the type the expression evaluates to without evaluating it.
+    /// Every variable needs to have its type declared in `types`.
+    pub fn check_with_var_types(
+        &self,
+        types: &HashMap<String, VarType>,
+    ) -> Result<ResultType, TypeError> {
+        check_types(self.emitter.rpn(), types)
+    }
+
+    /// Takes ownership of the reverse polish notation, variables are kept unbound.
+    pub fn into_rpn(self) -> Rpn {
+        self.emitter.into_rpn()
+    }
+
+    /// Returns the expression as a tree, variables are kept unbound.
+    pub fn ast(&self) -> Ast {
+        Ast::from_rpn(self.emitter.rpn()).expect("parsed rpn should form a single tree")
+    }
+
+    /// Draws the tree of the expression, see [`Ast::render_tree`].
+    pub fn render_tree(&self) -> String {
+        self.ast().render_tree()
+    }
+
+    /// Orders the operands of commutative operators, so that e.g. `1 + 2` and `2 + 1`
+    /// have the same reverse polish notation, see [`Ast::normalize`].
+    /// Non-commutative operators are untouched. Bound variables keep their values.
+    pub fn normalize(&mut self) {
+        let rpn = self.ast().normalize().into_rpn();
+        self.emitter.replace_rpn(rpn);
+    }
+
+    /// Removes boolean literals from `&&` and `||`, e.g. `true && {x} > 0` becomes `{x} > 0`,
+    /// see [`Ast::simplify_boolean`]. Bound variables keep their values.
+    pub fn simplify_boolean(&mut self) {
+        let rpn = self.ast().simplify_boolean().into_rpn();
+        self.emitter.replace_rpn(rpn);
+    }
+
+    /// Returns the derivative with respect to the variable `var`, see [`Ast::derivative`].
+    /// Bound variables keep their values.
+    pub fn derivative(&self, var: &str) -> Result<Expression, DerivativeError> {
+        let rpn = self.ast().derivative(var)?.into_rpn();
+        let mut derivative = self.clone();
+        derivative.emitter.replace_rpn(rpn);
+        Ok(derivative)
+    }
+
+    /// Replaces every number equal to `value` with the variable `name`, the inverse of binding.
+    /// Numbers are compared exactly as f32, so literals rounding to the same f32 match,
+    /// e.g. `0.30000001` and `0.3`, and `NaN` never matches.
+    /// Named constants were replaced by their values during parsing and match as well.
+    pub fn extract_constant(&mut self, value: f32, name: &str) {
+        let rpn = self
+            .emitter
+            .rpn()
+            .iter()
+            .map(|rpn_value| match rpn_value {
+                Value::Number(num) if *num == value => Value::Variable(name.to_owned()),
+                _ => rpn_value.clone(),
+            })
+            .collect();
+        self.emitter.replace_rpn(rpn);
+    }
+
     /// Evaluates the function. Panics if variables were not bound.
     pub fn eval(&self) -> Result<EmitResult, EvalError> {
         self.emitter.eval()
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use crate::expression::Expression;
-    use pretty_assertions::assert_eq;
-    use std::collections::HashMap;
 
-    #[test]
-    fn test_1() {
-        let expr = Expression::new("1+2").unwrap();
-        let res: f32 = expr.eval().unwrap().into();
+    /// Evaluates the function using `value_stack` as a reusable buffer,
+    /// useful when evaluating many times in a loop.
+    pub fn eval_into(
+        &self,
+        value_stack: &mut VecDeque<EmitResult>,
+    ) -> Result<EmitResult, EvalError> {
+        self.emitter.eval_into(value_stack)
+    }
 
-        assert_eq!(res, 3.0);
+    /// Evaluates an expression with `?` placeholders, e.g. `? + ? * ?`,
+    /// with the values in order. Fails with [`EvalError::PlaceholderCount`]
+    /// if the number of values differs from the number of placeholders.
+    pub fn eval_positional(&self, values: &[f32]) -> Result<EmitResult, EvalError> {
+        self.emitter.eval_positional(values)
     }
 
-    #[test]
-    fn test_2() {
-        let expr = Expression::new("1 + 2 * 3").unwrap();
-        let res: f32 = expr.eval().unwrap().into();
+    /// Evaluates the expression with variable values looked up by name,
+    /// values bound with `bind_variables` are ignored.
+    pub fn eval_with<F>(&self, lookup: F) -> Result<EmitResult, EvalError>
+    where
+        F: Fn(&str) -> Option<f32>,
+    {
+        self.emitter.eval_with(lookup)
+    }
 
-        assert_eq!(res, 7.0);
+    /// Evaluates the expression with the variable values, failing with [`EvalError::Timeout`]
+    /// if it takes longer than `timeout`, e.g. for formulas calling slow custom functions.
+    /// The time is checked after every step, values bound with `bind_variables` are ignored.
+    pub fn eval_with_timeout(
+        &self,
+        vars: &VariableMap,
+        timeout: Duration,
+    ) -> Result<EmitResult, EvalError> {
+        self.emitter
+            .eval_with_timeout(|name| vars.get(name).copied(), timeout)
     }
 
-    #[test]
-    fn test_3() {
-        let expr = Expression::new("(1 + 2) * 3").unwrap();
-        let res: f32 = expr.eval().unwrap().into();
+    /// Evaluates the expression with the variable values from `resolver`,
+    /// values bound with `bind_variables` are ignored.
+    pub fn eval_resolver(&self, resolver: &impl VariableResolver) -> Result<EmitResult, EvalError> {
+        self.eval_with(|name| resolver.resolve(name))
+    }
 
-        assert_eq!(res, 9.0);
+    /// Returns the result of an expression without variables,
+    /// `None` if it has variables or fails to evaluate.
+    pub fn as_constant(&self) -> Option<EmitResult> {
+        if !self.variable_counts().is_empty() {
+            return None;
+        }
+        self.eval_with(|_| None).ok()
     }
 
-    #[test]
-    fn test_4() {
-        let expr = Expression::new("5 * (3 + 4) - 2").unwrap();
-        let res: f32 = expr.eval().unwrap().into();
+    /// Compares the numeric results of two expressions without variables.
+    /// Returns `None` if either has variables, fails to evaluate, or does not result in a number.
+    pub fn partial_cmp_value(&self, other: &Expression) -> Option<std::cmp::Ordering> {
+        let constant_number = |expr: &Expression| match expr.as_constant() {
+            Some(EmitResult::Number(num)) => Some(num),
+            _ => None,
+        };
 
-        assert_eq!(res, 33.0);
+        constant_number(self)?.partial_cmp(&constant_number(other)?)
     }
 
-    #[test]
-    fn test_5() {
-        let mut expr = Expression::new("{a} > {b} && ({c} <= {d} || {e} >= {f}/{g})").unwrap();
-        expr.bind_variables(&HashMap::from_iter(vec![
-            ("a".to_string(), 3.0),
-            ("b".to_string(), 5.0),
-            ("c".to_string(), 9.0),
-            ("d".to_string(), 4.0),
-            ("e".to_string(), 1.0),
-            ("f".to_string(), 9.0),
-            ("g".to_string(), 3.0),
-        ]))
-        .unwrap();
-        let false_res: bool = expr.eval().unwrap().into();
+    /// Compares the reverse polish notations value by value, numbers are equal
+    /// if they differ by at most `float_tol`. Bound variables are compared as variables.
+    pub fn structurally_equal(&self, other: &Expression, float_tol: f32) -> bool {
+        let (rpn, other_rpn) = (self.emitter.rpn(), other.emitter.rpn());
+        rpn.len() == other_rpn.len()
+            && rpn.iter().zip(other_rpn).all(|pair| match pair {
+                (Value::Number(a), Value::Number(b)) => (a - b).abs() <= float_tol,
+                (a, b) => a == b,
+            })
+    }
 
-        assert_eq!(false_res, false);
+    /// Returns a function evaluating a copy of the expression with the variable values
+    /// of a map, independent of later changes to the expression.
+    /// Every variable is looked up once per call and written to its positions in the rpn,
+    /// see [`Expression::bind_values`], and the value stack is reused between calls.
+    /// Maps missing a variable, e.g. the left operand of `??`, are evaluated
+    /// like [`Expression::eval_with`].
+    pub fn compile(&self) -> impl Fn(&VariableMap) -> Result<EmitResult, EvalError> {
+        let names: Vec<String> = self
+            .variable_names()
+            .into_iter()
+            .map(str::to_owned)
+            .collect();
+        let emitter = RefCell::new(self.emitter.clone());
+        // values in the order of `names` and the value stack
+        let buffers = RefCell::new((Vec::with_capacity(names.len()), VecDeque::new()));
 
-        expr.bind_variables(&HashMap::from_iter(vec![
-            ("a".to_string(), 6.0),
-            ("b".to_string(), 5.0),
-            ("c".to_string(), 9.0),
-            ("d".to_string(), 4.0),
-            ("e".to_string(), 4.0),
-            ("f".to_string(), 9.0),
-            ("g".to_string(), 3.0),
-        ]))
-        .unwrap();
+        move |var_map| {
+            let (values, value_stack) = &mut *buffers.borrow_mut();
+            values.clear();
+            values.extend(names.iter().map_while(|name| var_map.get(name).copied()));
 
-        let true_res: bool = expr.eval().unwrap().into();
+            let mut emitter = emitter.borrow_mut();
+            if values.len() < names.len() {
+                return emitter.eval_with(|name| var_map.get(name).copied());
+            }
+            emitter
+                .bind_values(values)
+                .expect("there should be a value for every variable");
+            emitter.eval_into(value_stack)
+        }
+    }
 
-        assert_eq!(true_res, true);
+    /// Lazily evaluates the expression once for every variable map, see [`Expression::eval_with`].
+    pub fn eval_each<'a>(
+        &'a self,
+        maps: impl Iterator<Item = &'a VariableMap> + 'a,
+    ) -> impl Iterator<Item = Result<EmitResult, EvalError>> + 'a {
+        maps.map(|map| self.eval_with(|name| map.get(name).copied()))
     }
 
-    #[test]
-    fn test_6() {
-        let expr = Expression::new("true || (false && true)").unwrap();
-        let res: bool = expr.eval().unwrap().into();
+    /// Evaluates the expression with the variable values given as name-value pairs,
+    /// e.g. `expr.eval_with_slice(&[("x", 3.0), ("y", 4.0)])`.
+    pub fn eval_with_slice(&self, vars: &[(&str, f32)]) -> Result<EmitResult, EvalError> {
+        self.eval_with(|name| {
+            vars.iter()
+                .find(|(var_name, _)| *var_name == name)
+                .map(|(_, value)| *value)
+        })
+    }
 
-        assert_eq!(res, true);
+    /// Evaluates the expression with the variable values from `var_map`,
+    /// variables missing from the map have the `default` value.
+    pub fn eval_with_default(
+        &self,
+        var_map: &VariableMap,
+        default: f32,
+    ) -> Result<EmitResult, EvalError> {
+        self.eval_with(|name| Some(var_map.get(name).copied().unwrap_or(default)))
     }
 
-    #[test]
-    fn test_7() {
-        let expr = Expression::new("true || false && true").unwrap();
-        let res: bool = expr.eval().unwrap().into();
+    /// Evaluates the expression at `steps` evenly spaced values of `var` from `from` to `to`,
+    /// both included, with the other variables from `fixed`. Returns `(input, output)` pairs.
+    pub fn sweep(
+        &self,
+        var: &str,
+        from: f32,
+        to: f32,
+        steps: usize,
+        fixed: &VariableMap,
+    ) -> Result<Vec<(f32, EmitResult)>, EvalError> {
+        let step = if steps > 1 {
+            (to - from) / (steps - 1) as f32
+        } else {
+            0.0
+        };
 
-        assert_eq!(res, true);
+        (0..steps)
+            .map(|i| {
+                let input = from + step * i as f32;
+                let output = self.eval_with(|name| {
+                    if name == var {
+                        Some(input)
+                    } else {
+                        fixed.get(name).copied()
+                    }
+                })?;
+                Ok((input, output))
+            })
+            .collect()
+    }
+
+    /// Evaluates the expression with variables defined by expression strings,
+    /// which can use other defined variables and have to result in numbers.
+    /// Definitions are parsed with [`Expression::new`], values bound with
+    /// `bind_variables` are ignored.
+    pub fn eval_with_expr_vars(
+        &self,
+        defs: &HashMap<String, String>,
+    ) -> Result<EmitResult, ExprVarsError> {
+        let mut values = VariableMap::new();
+        for name in self.variable_names() {
+            resolve_expr_var(name, defs, &mut values, &mut vec![])?;
+        }
+
+        Ok(self.eval_with(|name| values.get(name).copied())?)
+    }
+
+    /// Evaluates the expression with the variables, constants, functions and options
+    /// of the context instead of the ones set on the expression.
+    pub fn eval_in(&self, ctx: &EvalContext) -> Result<EmitResult, EvalError> {
+        self.emitter.eval_in(ctx)
+    }
+
+    /// Evaluates the expression, which has to result in a number.
+    pub fn eval_number(&self) -> Result<f32, EvalError> {
+        match self.eval()? {
+            EmitResult::Number(num) => Ok(num),
+            result => Err(EvalError::WrongResultType {
+                expected: VarType::Number,
+                found: result.result_type(),
+            }),
+        }
+    }
+
+    /// Evaluates the expression to a number, a boolean result is converted to `1` or `0`,
+    /// e.g. for feeding results into numeric sinks. Lists are not converted.
+    pub fn eval_numeric_coerced(&self) -> Result<f32, EvalError> {
+        match self.eval()? {
+            EmitResult::Number(num) => Ok(num),
+            EmitResult::Boolean(boolean) => Ok(if boolean { 1.0 } else { 0.0 }),
+            result => Err(EvalError::WrongResultType {
+                expected: VarType::Number,
+                found: result.result_type(),
+            }),
+        }
+    }
+
+    /// Evaluates the expression, which has to result in a number, and clamps it to `[min, max]`.
+    /// Fails with [`EvalError::NaNResult`] if the result is `NaN`
+    /// and with [`EvalError::InvalidBounds`] if `min > max` or either bound is `NaN`.
+    pub fn eval_clamped(&self, min: f32, max: f32) -> Result<f32, EvalError> {
+        // `f32::clamp` panics for these bounds
+        if min > max || min.is_nan() || max.is_nan() {
+            return Err(EvalError::InvalidBounds { min, max });
+        }
+        let num = self.eval_number()?;
+        if num.is_nan() {
+            return Err(EvalError::NaNResult);
+        }
+
+        Ok(num.clamp(min, max))
+    }
+
+    /// Evaluates only the first `n` values of the reverse polish notation
+    /// and returns the value stack, the last element being the top of the stack.
+    pub fn eval_steps(&self, n: usize) -> Result<Vec<EmitResult>, EvalError> {
+        self.emitter.eval_steps(n)
+    }
+
+    /// Checks that the expression evaluates to a number, see [`TypedExpression`].
+    pub fn typed_number(self) -> Result<TypedExpression<f32>, TypeError> {
+        TypedExpression::new(self)
+    }
+
+    /// Checks that the expression evaluates to a boolean, see [`TypedExpression`].
+    pub fn typed_boolean(self) -> Result<TypedExpression<bool>, TypeError> {
+        TypedExpression::new(self)
+    }
+}
+
+/// Rust type an expression result can be converted to.
+pub trait TypedResult: Sized {
+    const RESULT_TYPE: ResultType;
+
+    /// returns `None` if the result has a different type
+    fn from_result(result: EmitResult) -> Option<Self>;
+}
+
+impl TypedResult for f32 {
+    const RESULT_TYPE: ResultType = VarType::Number;
+
+    fn from_result(result: EmitResult) -> Option<Self> {
+        match result {
+            EmitResult::Number(num) => Some(num),
+            _ => None,
+        }
+    }
+}
+
+impl TypedResult for bool {
+    const RESULT_TYPE: ResultType = VarType::Boolean;
+
+    fn from_result(result: EmitResult) -> Option<Self> {
+        match result {
+            EmitResult::Boolean(boolean) => Some(boolean),
+            _ => None,
+        }
+    }
+}
+
+/// Expression whose result type was checked when it was created, variables are assumed to be numbers.
+/// If a variable is bound to a boolean, e.g. from JSON, evaluation can still return
+/// [`EvalError::WrongResultType`].
+/// Evaluation settings which could change the result type, like coercion, cannot be changed.
+#[derive(Debug, Clone)]
+pub struct TypedExpression<T> {
+    expr: Expression,
+    result_type: PhantomData<T>,
+}
+
+impl<T: TypedResult> TypedExpression<T> {
+    fn new(expr: Expression) -> Result<Self, TypeError> {
+        let var_types = expr
+            .variable_counts()
+            .into_keys()
+            .map(|name| (name, VarType::Number))
+            .collect();
+        let found = expr.check_with_var_types(&var_types)?;
+        if found != T::RESULT_TYPE {
+            return Err(TypeError::WrongResultType {
+                expected: T::RESULT_TYPE,
+                found,
+            });
+        }
+
+        Ok(Self {
+            expr,
+            result_type: PhantomData,
+        })
+    }
+
+    /// see [`Expression::bind_variables`]
+    pub fn bind_variables(&mut self, var_map: &VariableMap) -> Result<(), BindVariablesError> {
+        self.expr.bind_variables(var_map)
+    }
+
+    pub fn eval(&self) -> Result<T, EvalError> {
+        self.expr.eval().and_then(Self::convert)
+    }
+
+    /// see [`Expression::eval_with`]
+    pub fn eval_with<F>(&self, lookup: F) -> Result<T, EvalError>
+    where
+        F: Fn(&str) -> Option<f32>,
+    {
+        self.expr.eval_with(lookup).and_then(Self::convert)
+    }
+
+    /// Returns the underlying expression.
+    pub fn into_inner(self) -> Expression {
+        self.expr
+    }
+
+    // the checked type only holds for number variables
+    fn convert(result: EmitResult) -> Result<T, EvalError> {
+        let found = result.result_type();
+        T::from_result(result).ok_or(EvalError::WrongResultType {
+            expected: T::RESULT_TYPE,
+            found,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ast::{Ast, DerivativeError},
+        context::{
+            Associativity, EvalContext, FunctionRegistry, OperatorTable, UnboundVariables,
+            VariableResolver,
+        },
+        emitter::{AngleMode, BindVariablesError, EmitResult, EvalError},
+        expression::{ExprVarsError, Expression, ExpressionCreationError, Limits, TypedExpression},
+        parser::{FunctionType, OperatorType, ParserError, Value},
+        tokenizer::describe_tokens,
+        type_checker::{TypeError, VarType},
+    };
+    use pretty_assertions::assert_eq;
+    use proptest::prelude::*;
+    use std::{
+        collections::{HashMap, HashSet, VecDeque},
+        time::Duration,
+    };
+
+    #[test]
+    fn test_1() {
+        let expr = Expression::new("1+2").unwrap();
+        let res: f32 = expr.eval().unwrap().into();
+
+        assert_eq!(res, 3.0);
+    }
+
+    #[test]
+    fn test_2() {
+        let expr = Expression::new("1 + 2 * 3").unwrap();
+        let res: f32 = expr.eval().unwrap().into();
+
+        assert_eq!(res, 7.0);
+    }
+
+    #[test]
+    fn test_3() {
+        let expr = Expression::new("(1 + 2) * 3").unwrap();
+        let res: f32 = expr.eval().unwrap().into();
+
+        assert_eq!(res, 9.0);
+    }
+
+    #[test]
+    fn test_4() {
+        let expr = Expression::new("5 * (3 + 4) - 2").unwrap();
+        let res: f32 = expr.eval().unwrap().into();
+
+        assert_eq!(res, 33.0);
+    }
+
+    #[test]
+    fn test_5() {
+        let mut expr = Expression::new("{a} > {b} && ({c} <= {d} || {e} >= {f}/{g})").unwrap();
+        expr.bind_variables(&HashMap::from_iter(vec![
+            ("a".to_string(), 3.0),
+            ("b".to_string(), 5.0),
+            ("c".to_string(), 9.0),
+            ("d".to_string(), 4.0),
+            ("e".to_string(), 1.0),
+            ("f".to_string(), 9.0),
+            ("g".to_string(), 3.0),
+        ]))
+        .unwrap();
+        let false_res: bool = expr.eval().unwrap().into();
+
+        assert_eq!(false_res, false);
+
+        expr.bind_variables(&HashMap::from_iter(vec![
+            ("a".to_string(), 6.0),
+            ("b".to_string(), 5.0),
+            ("c".to_string(), 9.0),
+            ("d".to_string(), 4.0),
+            ("e".to_string(), 4.0),
+            ("f".to_string(), 9.0),
+            ("g".to_string(), 3.0),
+        ]))
+        .unwrap();
+
+        let true_res: bool = expr.eval().unwrap().into();
+
+        assert_eq!(true_res, true);
+    }
+
+    #[test]
+    fn test_6() {
+        let expr = Expression::new("true || (false && true)").unwrap();
+        let res: bool = expr.eval().unwrap().into();
+
+        assert_eq!(res, true);
+    }
+
+    #[test]
+    fn test_7() {
+        let expr = Expression::new("true || false && true").unwrap();
+        let res: bool = expr.eval().unwrap().into();
+
+        assert_eq!(res, true);
+    }
+
+    #[test]
+    fn test_integer_equality() {
+        let mut expr = Expression::new("4/2 = 2").unwrap();
+        expr.set_integer_equality(true);
+        let res: bool = expr.eval().unwrap().into();
+        assert_eq!(res, true);
+
+        let mut expr = Expression::new("5/2 = 2").unwrap();
+        expr.set_integer_equality(true);
+        let res: bool = expr.eval().unwrap().into();
+        assert_eq!(res, false);
+    }
+
+    #[test]
+    fn test_check_with_var_types() {
+        let expr = Expression::new("{flag} && {x} > 0").unwrap();
+        let types = HashMap::from_iter(vec![
+            ("x".to_string(), VarType::Number),
+            ("flag".to_string(), VarType::Boolean),
+        ]);
+        assert_eq!(expr.check_with_var_types(&types), Ok(VarType::Boolean));
+
+        let expr = Expression::new("{flag} + {x}").unwrap();
+        assert_eq!(
+            expr.check_with_var_types(&types),
+            Err(TypeError::OperatorMismatch {
+                operator: OperatorType::Plus,
+                left: VarType::Boolean,
+                right: VarType::Number,
+            })
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        let res: f32 = Expression::new("sign(0 - 3)")
+            .unwrap()
+            .eval()
+            .unwrap()
+            .into();
+        assert_eq!(res, -1.0);
+
+        let res: f32 = Expression::new("sign(0)").unwrap().eval().unwrap().into();
+        assert_eq!(res, 0.0);
+
+        let res: f32 = Expression::new("sign(5)").unwrap().eval().unwrap().into();
+        assert_eq!(res, 1.0);
+
+        let mut expr = Expression::new("abs({x} - 10) * 2").unwrap();
+        expr.bind_variables(&HashMap::from_iter(vec![("x".to_string(), 4.0)]))
+            .unwrap();
+        let res: f32 = expr.eval().unwrap().into();
+        assert_eq!(res, 12.0);
+    }
+
+    #[test]
+    fn test_nested_functions() {
+        let eval = |expr: &str| Expression::new(expr).unwrap().eval_number();
+
+        assert_eq!(eval("max(min(1, 2), sqrt(9))"), Ok(3.0));
+        assert_eq!(eval("min(max(1, 2), 3) + max(0, 1)"), Ok(3.0));
+        assert_eq!(eval("max(min(5, sqrt(16)), abs(0 - 2)) * 2"), Ok(8.0));
+        assert_eq!(eval("min(sqrt(max(4, min(9, 16))), 10)"), Ok(3.0));
+        assert_eq!(eval("max(1, min(2, max(3, min(4, 5))))"), Ok(2.0));
+    }
+
+    #[test]
+    fn test_builder() {
+        let builder = Expression::builder()
+            .constants(HashMap::from_iter(vec![("pi".to_string(), 3.0)]))
+            .variable_delimiters("[", "]");
+
+        let mut expr = builder.build("2 * pi + [x]").unwrap();
+        expr.bind_variables(&HashMap::from_iter(vec![("x".to_string(), 1.0)]))
+            .unwrap();
+        let res: f32 = expr.eval().unwrap().into();
+        assert_eq!(res, 7.0);
+
+        let builder = Expression::builder().limits(Limits {
+            max_length: Some(8),
+            max_tokens: Some(3),
+        });
+        assert!(builder.build("1 + 2").is_ok());
+        assert!(matches!(
+            builder.build("1 + 2 + 3"),
+            Err(ExpressionCreationError::TooLong { max: 8, found: 9 })
+        ));
+        assert!(matches!(
+            builder.build("1+2+3"),
+            Err(ExpressionCreationError::TooManyTokens { max: 3, found: 5 })
+        ));
+    }
+
+    #[test]
+    fn test_coercion() {
+        let mut expr = Expression::new("({x} > 0) + ({y} > 0)").unwrap();
+        expr.set_coercion(true);
+        expr.bind_variables(&HashMap::from_iter(vec![
+            ("x".to_string(), 1.0),
+            ("y".to_string(), -1.0),
+        ]))
+        .unwrap();
+        let res: f32 = expr.eval().unwrap().into();
+        assert_eq!(res, 1.0);
+
+        let mut expr = Expression::new("{x} && true").unwrap();
+        expr.set_coercion(true);
+        expr.bind_variables(&HashMap::from_iter(vec![("x".to_string(), 4.0)]))
+            .unwrap();
+        let res: bool = expr.eval().unwrap().into();
+        assert_eq!(res, true);
+    }
+
+    #[test]
+    fn test_radix_numbers() {
+        let res: f32 = Expression::new("0xFF & 0x0F")
+            .unwrap()
+            .eval()
+            .unwrap()
+            .into();
+        assert_eq!(res, 15.0);
+
+        let res: f32 = Expression::new("0b1010 + 1")
+            .unwrap()
+            .eval()
+            .unwrap()
+            .into();
+        assert_eq!(res, 11.0);
+
+        let res: bool = Expression::new("(0b1100 & 0b0110) = 4")
+            .unwrap()
+            .eval()
+            .unwrap()
+            .into();
+        assert_eq!(res, true);
+    }
+
+    #[test]
+    fn test_validate_variables() {
+        let allowed = HashSet::from_iter(vec!["a".to_string()]);
+
+        let expr = Expression::new("{a} + {secret}").unwrap();
+        assert_eq!(
+            expr.validate_variables(&allowed),
+            Err(vec!["secret".to_string()])
+        );
+
+        let expr = Expression::new("{a} * 2 + {a}").unwrap();
+        assert_eq!(expr.validate_variables(&allowed), Ok(()));
+    }
+
+    #[test]
+    fn test_bind_variables_missing() {
+        let mut expr = Expression::new("{z} + {a} + {z}").unwrap();
+        assert_eq!(
+            expr.bind_variables(&HashMap::from_iter(vec![("b".to_string(), 1.0)])),
+            Err(BindVariablesError::VariablesDoNotExist(vec![
+                "z".to_string(),
+                "a".to_string()
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_eval_number() {
+        let expr = Expression::new("1 + 2").unwrap();
+        assert_eq!(expr.eval_number(), Ok(3.0));
+
+        let expr = Expression::new("2 + .25").unwrap();
+        assert_eq!(expr.eval_number(), Ok(2.25));
+
+        let expr = Expression::new("1 > 2").unwrap();
+        assert_eq!(
+            expr.eval_number(),
+            Err(EvalError::WrongResultType {
+                expected: VarType::Number,
+                found: VarType::Boolean,
+            })
+        );
+    }
+
+    #[test]
+    fn test_eval_numeric_coerced() {
+        let eval = |expr: &str| Expression::new(expr).unwrap().eval_numeric_coerced();
+
+        assert_eq!(eval("1 > 0"), Ok(1.0));
+        assert_eq!(eval("1 < 0"), Ok(0.0));
+        assert_eq!(eval("1 + 2"), Ok(3.0));
+        assert_eq!(
+            eval("(1, 2)"),
+            Err(EvalError::WrongResultType {
+                expected: VarType::Number,
+                found: VarType::List,
+            })
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_bind_json() {
+        let mut expr = Expression::new("{enabled} && {price} * {count} > 10").unwrap();
+
+        let rule = serde_json::json!({"enabled": true, "price": 2.5, "count": 5, "name": "rule"});
+        expr.bind_json(&rule).unwrap();
+        assert_eq!(expr.eval(), Ok(EmitResult::Boolean(true)));
+
+        let rule = serde_json::json!({"enabled": false, "price": 2.5, "count": 5});
+        expr.bind_json(&rule).unwrap();
+        assert_eq!(expr.eval(), Ok(EmitResult::Boolean(false)));
+
+        // booleans keep their type
+        let mut sum = Expression::new("{enabled} + 1").unwrap();
+        sum.bind_json(&serde_json::json!({"enabled": true}))
+            .unwrap();
+        assert!(matches!(
+            sum.eval(),
+            Err(EvalError::TypeMismatch {
+                left: VarType::Boolean,
+                ..
+            })
+        ));
+
+        assert_eq!(
+            expr.bind_json(&serde_json::json!({"enabled": true, "price": "2.5", "count": 5})),
+            Err(BindVariablesError::UnsupportedJsonValue(
+                "price".to_string()
+            ))
+        );
+        assert_eq!(
+            expr.bind_json(&serde_json::json!({"enabled": true, "count": 5})),
+            Err(BindVariablesError::VariablesDoNotExist(vec![
+                "price".to_string()
+            ]))
+        );
+        assert_eq!(
+            expr.bind_json(&serde_json::json!([1, 2])),
+            Err(BindVariablesError::NotAnObject)
+        );
+    }
+
+    #[test]
+    fn test_update_variable() {
+        let mut expr = Expression::new("{x} * {x} + {y}").unwrap();
+        expr.bind_variables(&HashMap::from_iter(vec![
+            ("x".to_string(), 0.0),
+            ("y".to_string(), 1.0),
+        ]))
+        .unwrap();
+
+        for x in 1..4 {
+            expr.update_variable("x", x as f32).unwrap();
+            assert_eq!(expr.eval_number(), Ok((x * x + 1) as f32));
+        }
+    }
+
+    #[test]
+    fn test_bind_values() {
+        let mut expr = Expression::new("{x} * {y} + {x} - {z} / {y}").unwrap();
+        assert_eq!(expr.variable_names(), vec!["x", "y", "z"]);
+
+        for i in 0..10_000 {
+            let (x, y, z) = (i as f32, (i % 7 + 1) as f32, (i % 3) as f32);
+            expr.bind_values(&[x, y, z]).unwrap();
+            assert_eq!(expr.eval_number(), Ok(x * y + x - z / y));
+        }
+
+        let var_map = HashMap::from([
+            ("x".to_string(), 2.0),
+            ("y".to_string(), 4.0),
+            ("z".to_string(), 8.0),
+        ]);
+        let mut bound = expr.clone();
+        bound.bind_variables(&var_map).unwrap();
+        expr.bind_values(&[2.0, 4.0, 8.0]).unwrap();
+        assert_eq!(expr.eval(), bound.eval());
+
+        assert_eq!(
+            expr.bind_values(&[1.0]),
+            Err(BindVariablesError::WrongValueCount {
+                expected: 3,
+                found: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_signed_zero() {
+        let eval = |expr: &str, z: f32| {
+            Expression::new(expr)
+                .unwrap()
+                .eval_with_slice(&[("z", z)])
+                .unwrap()
+        };
+        assert_eq!(eval("1 / (0 - 0)", 0.0), EmitResult::Number(f32::INFINITY));
+        assert_eq!(eval("{z} = 0", -0.0), EmitResult::Boolean(true));
+        assert_eq!(eval("{z} < 0", -0.0), EmitResult::Boolean(false));
+        // the sign is kept through arithmetic
+        assert_eq!(eval("1 / {z}", -0.0), EmitResult::Number(f32::NEG_INFINITY));
+        assert_eq!(
+            eval("1 / ({z} * 2)", -0.0),
+            EmitResult::Number(f32::NEG_INFINITY)
+        );
+
+        let mut expr = Expression::new("1 / {z}").unwrap();
+        expr.set_unsigned_zero(true);
+        assert_eq!(
+            expr.eval_with_slice(&[("z", -0.0)]),
+            Ok(EmitResult::Number(f32::INFINITY))
+        );
+        expr.bind_variables(&HashMap::from([("z".to_string(), -0.0)]))
+            .unwrap();
+        assert_eq!(expr.eval(), Ok(EmitResult::Number(f32::INFINITY)));
+        let EmitResult::Number(zero) = expr.eval_steps(1).unwrap()[0] else {
+            panic!("variable should be a number");
+        };
+        assert!(zero.is_sign_positive());
+    }
+
+    #[test]
+    fn test_eval_with_timeout() {
+        let mut functions = FunctionRegistry::new();
+        functions.register("slow", 1, |args| {
+            std::thread::sleep(Duration::from_millis(30));
+            args[0]
+        });
+        let expr = Expression::builder()
+            .functions(functions)
+            .build("slow({x}) + slow(2) + slow(3)")
+            .unwrap();
+        let vars = HashMap::from([("x".to_string(), 1.0)]);
+
+        assert_eq!(
+            expr.eval_with_timeout(&vars, Duration::from_millis(10)),
+            Err(EvalError::Timeout(Duration::from_millis(10)))
+        );
+        assert_eq!(
+            expr.eval_with_timeout(&vars, Duration::from_secs(60)),
+            Ok(EmitResult::Number(6.0))
+        );
+        assert_eq!(
+            expr.eval_with_timeout(&HashMap::new(), Duration::from_secs(60)),
+            Err(EvalError::UnboundVariable("x".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_max_steps() {
+        let sum = vec!["1"; 1000].join(" + ");
+        let mut expr = Expression::new(&sum).unwrap();
+        assert_eq!(expr.eval(), Ok(EmitResult::Number(1000.0)));
+
+        expr.set_max_steps(Some(100));
+        assert_eq!(expr.eval(), Err(EvalError::StepLimitExceeded(100)));
+        assert_eq!(
+            expr.eval_with(|_| None),
+            Err(EvalError::StepLimitExceeded(100))
+        );
+        // 1000 numbers and 999 additions
+        expr.set_max_steps(Some(1999));
+        assert_eq!(expr.eval(), Ok(EmitResult::Number(1000.0)));
+    }
+
+    #[test]
+    fn test_check_binding() {
+        let vars = HashMap::from([("flag".to_string(), 1.0), ("x".to_string(), 2.0)]);
+
+        assert_eq!(
+            Expression::new("{flag} && true")
+                .unwrap()
+                .check_binding(&vars),
+            Err(vec!["flag".to_string()])
+        );
+        assert_eq!(
+            Expression::new("if({x}, {flag}, 0) => first_true({flag}, {y})")
+                .unwrap()
+                .check_binding(&vars),
+            Err(vec!["x".to_string(), "flag".to_string()])
+        );
+        assert_eq!(
+            Expression::new("{flag} > 0 && {x} + 1 > 2")
+                .unwrap()
+                .check_binding(&vars),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_nan_comparison() {
+        let mut expr = Expression::new("(0/0) = (0/0)").unwrap();
+        let res: bool = expr.eval().unwrap().into();
+        assert_eq!(res, false);
+
+        expr.set_strict_nan(true);
+        assert_eq!(expr.eval(), Err(EvalError::NaNComparison(OperatorType::Eq)));
+    }
+
+    #[test]
+    fn test_factorial() {
+        assert_eq!(Expression::new("5!").unwrap().eval_number(), Ok(120.0));
+        assert_eq!(Expression::new("0!").unwrap().eval_number(), Ok(1.0));
+        assert_eq!(Expression::new("(2+1)!").unwrap().eval_number(), Ok(6.0));
+        assert_eq!(
+            Expression::new("2 * 3! - 1").unwrap().eval_number(),
+            Ok(11.0)
+        );
+        assert_eq!(
+            Expression::new("(5/2)!").unwrap().eval_number(),
+            Err(EvalError::InvalidFactorial(2.5))
+        );
+    }
+
+    #[test]
+    fn test_ast() {
+        let expr = Expression::new("1 + 2 * 3").unwrap();
+        assert_eq!(
+            expr.ast(),
+            Ast::BinaryOp {
+                operator: OperatorType::Plus,
+                left: Box::new(Ast::Number(1.0)),
+                right: Box::new(Ast::BinaryOp {
+                    operator: OperatorType::Times,
+                    left: Box::new(Ast::Number(2.0)),
+                    right: Box::new(Ast::Number(3.0)),
+                }),
+            }
+        );
+        assert_eq!(expr.ast().to_string(), "+\n  1\n  *\n    2\n    3\n");
+    }
+
+    #[test]
+    fn test_type_mismatch() {
+        assert_eq!(
+            Expression::new("(1 > 0) + 1").unwrap().eval(),
+            Err(EvalError::TypeMismatch {
+                operator: OperatorType::Plus,
+                left: VarType::Boolean,
+                right: VarType::Number,
+            })
+        );
+        assert_eq!(
+            Expression::new("(1 > 0) && (2 = 2)").unwrap().eval(),
+            Ok(EmitResult::Boolean(true))
+        );
+    }
+
+    #[test]
+    fn test_with_bindings() {
+        let template = Expression::new("{x} * 2 + {y}").unwrap();
+
+        let first = template
+            .with_bindings(&HashMap::from([
+                ("x".to_string(), 1.0),
+                ("y".to_string(), 1.0),
+            ]))
+            .unwrap();
+        let second = template
+            .with_bindings(&HashMap::from([
+                ("x".to_string(), 5.0),
+                ("y".to_string(), 0.0),
+            ]))
+            .unwrap();
+        assert_eq!(first.eval_number(), Ok(3.0));
+        assert_eq!(second.eval_number(), Ok(10.0));
+        assert_eq!(template.eval(), Err(EvalError::NoVariables));
+
+        assert_eq!(
+            template
+                .with_bindings(&HashMap::from([("x".to_string(), 1.0)]))
+                .unwrap_err(),
+            BindVariablesError::VariablesDoNotExist(vec!["y".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_decimal_comma() {
+        let builder = Expression::builder().decimal_comma(true);
+        assert_eq!(builder.build("1,5 + 2,5").unwrap().eval_number(), Ok(4.0));
+        assert_eq!(
+            builder.build("abs(0 - 0,25)").unwrap().eval_number(),
+            Ok(0.25)
+        );
+
+        // without the option the comma separates arguments
+        assert!(Expression::new("1,5 + 2,5").is_err());
+    }
+
+    #[test]
+    fn test_new_many() {
+        let exprs = Expression::new_many(&["1 + 2", "2 * (3", "{x} > 1"]);
+        assert_eq!(exprs.len(), 3);
+
+        assert_eq!(exprs[0].as_ref().unwrap().eval_number(), Ok(3.0));
+        assert!(matches!(
+            exprs[1],
+            Err(ExpressionCreationError::ParserError(_))
+        ));
+        assert_eq!(exprs[2].as_ref().unwrap().str_expr, "{x} > 1");
+    }
+
+    #[test]
+    fn test_into_rpn() {
+        assert_eq!(
+            Expression::new("1 + 2").unwrap().into_rpn(),
+            vec![
+                Value::Number(1.0),
+                Value::Number(2.0),
+                Value::Operator(OperatorType::Plus),
+            ]
+        );
+
+        let mut expr = Expression::new("{x} * 2").unwrap();
+        expr.update_variable("x", 3.0).unwrap();
+        assert_eq!(
+            expr.into_rpn(),
+            vec![
+                Value::Variable("x".to_string()),
+                Value::Number(2.0),
+                Value::Operator(OperatorType::Times),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_double_equals() {
+        assert_eq!(
+            Expression::new("1 == 1").unwrap().eval(),
+            Ok(EmitResult::Boolean(true))
+        );
+
+        let mut expr = Expression::new("{x} == {y}").unwrap();
+        expr.bind_variables(&HashMap::from([
+            ("x".to_string(), 2.0),
+            ("y".to_string(), 3.0),
+        ]))
+        .unwrap();
+        assert_eq!(expr.eval(), Ok(EmitResult::Boolean(false)));
+        assert_eq!(
+            Expression::new("{x} == {y}").unwrap().into_rpn(),
+            Expression::new("{x} = {y}").unwrap().into_rpn()
+        );
+    }
+
+    #[test]
+    fn test_eval_with_slice() {
+        let expr = Expression::new("{x} * {x} + {y} * {y} = 25").unwrap();
+        assert_eq!(
+            expr.eval_with_slice(&[("x", 3.0), ("y", 4.0)]),
+            Ok(EmitResult::Boolean(true))
+        );
+        assert_eq!(
+            expr.eval_with_slice(&[("x", 3.0)]),
+            Err(EvalError::UnboundVariable("y".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_eval_into() {
+        let mut expr = Expression::new("{x} > 2 && {x} < 4").unwrap();
+        let mut buffer = VecDeque::new();
+        let results: Vec<EmitResult> = (0..6)
+            .map(|x| {
+                expr.update_variable("x", x as f32).unwrap();
+                expr.eval_into(&mut buffer).unwrap()
+            })
+            .collect();
+        assert_eq!(
+            results,
+            [false, false, false, true, false, false].map(EmitResult::Boolean)
+        );
+    }
+
+    #[test]
+    fn test_if_function() {
+        let eval = |expr: &str| Expression::new(expr).unwrap().eval();
+
+        assert_eq!(eval("if(1 > 0, 10, 20)"), Ok(EmitResult::Number(10.0)));
+        assert_eq!(eval("if(false, 1, 2)"), Ok(EmitResult::Number(2.0)));
+        assert_eq!(
+            eval("if(2 > 1, 1 = 1, false) && true"),
+            Ok(EmitResult::Boolean(true))
+        );
+        assert_eq!(
+            eval("max(if(true, 3, 0), 2) * 2"),
+            Ok(EmitResult::Number(6.0))
+        );
+
+        let types = HashMap::new();
+        assert_eq!(
+            Expression::new("if(true, 1, 2) + 1")
+                .unwrap()
+                .check_with_var_types(&types),
+            Ok(VarType::Number)
+        );
+        assert_eq!(
+            Expression::new("if(true, 1, false)")
+                .unwrap()
+                .check_with_var_types(&types),
+            Err(TypeError::ArgumentMismatch {
+                function: FunctionType::If,
+                expected: VarType::Number,
+                found: VarType::Boolean,
+            })
+        );
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(
+            Expression::new("2 * {x} + 3.5").unwrap().literals(),
+            vec![2.0, 3.5]
+        );
+        assert_eq!(
+            Expression::new("max(0xFF, 1) > 2 * (3 + {y})")
+                .unwrap()
+                .literals(),
+            vec![255.0, 1.0, 2.0, 3.0]
+        );
+
+        let mut expr = Expression::new("{x} + {y}").unwrap();
+        expr.bind_values(&[1.0, 2.0]).unwrap();
+        assert_eq!(expr.literals(), Vec::<f32>::new());
+    }
+
+    #[test]
+    fn test_variable_counts() {
+        assert_eq!(
+            Expression::new("{x} + {x} * {y}")
+                .unwrap()
+                .variable_counts(),
+            HashMap::from([("x".to_string(), 2), ("y".to_string(), 1)])
+        );
+        assert!(Expression::new("1 + 2")
+            .unwrap()
+            .variable_counts()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_eval_with_default() {
+        let expr = Expression::new("{a} + {b}").unwrap();
+        let var_map = HashMap::from([("a".to_string(), 2.0)]);
+
+        assert_eq!(
+            expr.eval_with_default(&var_map, 0.0),
+            Ok(EmitResult::Number(2.0))
+        );
+        assert_eq!(
+            expr.eval_with_default(&var_map, 1.5),
+            Ok(EmitResult::Number(3.5))
+        );
+        assert_eq!(
+            expr.eval_with_default(&HashMap::new(), 1.0),
+            Ok(EmitResult::Number(2.0))
+        );
+    }
+
+    #[test]
+    fn test_normalize() {
+        let normalized = |expr: &str| {
+            let mut expr = Expression::new(expr).unwrap();
+            expr.normalize();
+            expr.into_rpn()
+        };
+
+        assert_eq!(normalized("1 + 2"), normalized("2 + 1"));
+        assert_eq!(
+            normalized("1 + 2"),
+            vec![
+                Value::Number(1.0),
+                Value::Number(2.0),
+                Value::Operator(OperatorType::Plus),
+            ]
+        );
+        assert_ne!(normalized("1 - 2"), normalized("2 - 1"));
+
+        let mut expr = Expression::new("{y} / {x}").unwrap();
+        expr.update_variable("x", 2.0).unwrap();
+        expr.update_variable("y", 1.0).unwrap();
+        expr.normalize();
+        assert_eq!(expr.eval_number(), Ok(0.5));
+    }
+
+    #[test]
+    fn test_eval_positional() {
+        let expr = Expression::new("? + ?").unwrap();
+        assert_eq!(
+            expr.eval_positional(&[3.0, 4.0]),
+            Ok(EmitResult::Number(7.0))
+        );
+        assert_eq!(
+            expr.eval_positional(&[3.0]),
+            Err(EvalError::PlaceholderCount {
+                expected: 2,
+                found: 1
+            })
+        );
+        assert_eq!(expr.eval(), Err(EvalError::UnfilledPlaceholder(0)));
+
+        let expr = Expression::new("? + ? * ?").unwrap();
+        assert_eq!(
+            expr.eval_positional(&[1.0, 2.0, 3.0]),
+            Ok(EmitResult::Number(7.0))
+        );
+
+        let mut expr = Expression::new("max(?, {x}) > ?").unwrap();
+        expr.bind_variables(&HashMap::from([("x".to_string(), 5.0)]))
+            .unwrap();
+        assert_eq!(
+            expr.eval_positional(&[1.0, 4.0]),
+            Ok(EmitResult::Boolean(true))
+        );
+        assert_eq!(
+            expr.eval_positional(&[6.0, 6.0]),
+            Ok(EmitResult::Boolean(false))
+        );
+    }
+
+    #[test]
+    fn test_implies() {
+        let eval = |expr: &str| Expression::new(expr).unwrap().eval();
+
+        assert_eq!(eval("true => false"), Ok(EmitResult::Boolean(false)));
+        assert_eq!(eval("false => true"), Ok(EmitResult::Boolean(true)));
+        assert_eq!(eval("true => true"), Ok(EmitResult::Boolean(true)));
+        assert_eq!(eval("false => false"), Ok(EmitResult::Boolean(true)));
+        // `||` binds tighter, `(false || true) => false`
+        assert_eq!(
+            eval("false || true => false"),
+            Ok(EmitResult::Boolean(false))
+        );
+        assert_eq!(
+            Expression::new("{x} > 1 => {x} > 0")
+                .unwrap()
+                .eval_with_slice(&[("x", 0.5)]),
+            Ok(EmitResult::Boolean(true))
+        );
+    }
+
+    #[test]
+    fn test_coalesce() {
+        let expr = Expression::new("{missing} ?? 5").unwrap();
+        assert_eq!(expr.eval_with_slice(&[]), Ok(EmitResult::Number(5.0)));
+        assert_eq!(expr.eval(), Ok(EmitResult::Number(5.0)));
+
+        let expr = Expression::new("({present} ?? 5) * 2").unwrap();
+        assert_eq!(
+            expr.eval_with_slice(&[("present", 3.0)]),
+            Ok(EmitResult::Number(6.0))
+        );
+        assert_eq!(expr.eval_with_slice(&[]), Ok(EmitResult::Number(10.0)));
+
+        assert_eq!(
+            Expression::new("{x} ?? true")
+                .unwrap()
+                .check_with_var_types(&HashMap::from([("x".to_string(), VarType::Number)])),
+            Err(TypeError::OperatorMismatch {
+                operator: OperatorType::Coalesce,
+                left: VarType::Number,
+                right: VarType::Boolean,
+            })
+        );
+    }
+
+    #[test]
+    fn test_eval_in() {
+        let mut functions = FunctionRegistry::new();
+        functions.register("hypot", 2, |args| args[0].hypot(args[1]));
+        let expr = Expression::builder()
+            .functions(functions.clone())
+            .build("hypot({x}, 4) * {scale}")
+            .unwrap();
+        // the function registered on the builder is used by `eval`
+        assert_eq!(
+            expr.eval_with_slice(&[("x", 3.0), ("scale", 2.0)]),
+            Ok(EmitResult::Number(10.0))
+        );
+
+        let mut ctx = EvalContext {
+            variables: HashMap::from([("x".to_string(), 3.0)]),
+            constants: HashMap::from([("scale".to_string(), 1.0)]),
+            functions,
+            ..Default::default()
+        };
+        assert_eq!(expr.eval_in(&ctx), Ok(EmitResult::Number(5.0)));
+
+        ctx.functions = FunctionRegistry::new();
+        assert_eq!(
+            expr.eval_in(&ctx),
+            Err(EvalError::UnknownFunction("hypot".to_string()))
+        );
+        ctx.variables.clear();
+        assert_eq!(
+            expr.eval_in(&ctx),
+            Err(EvalError::UnboundVariable("x".to_string()))
+        );
+
+        assert_eq!(
+            Expression::new("hypot(3, 4)").unwrap_err().to_string(),
+            "unknown function: 'hypot'"
+        );
+    }
+
+    #[test]
+    fn test_custom_operators() {
+        let mut operators = OperatorTable::new();
+        operators.register("%%", 8, Associativity::Left, |a, b| (a + b) / 2.0);
+        operators.register("**", 9, Associativity::Right, f32::powf);
+        let builder = Expression::builder().operators(operators);
+        let eval = |expr: &str| builder.build(expr).unwrap().eval();
+
+        assert_eq!(eval("4 %% 6"), Ok(EmitResult::Number(5.0)));
+        assert_eq!(eval("1 + 4 %% 6"), Ok(EmitResult::Number(6.0)));
+        assert_eq!(eval("2 ** 3 ** 2"), Ok(EmitResult::Number(512.0)));
+        assert_eq!(eval("2 * 3 ** 2 > 17"), Ok(EmitResult::Boolean(true)));
+        assert_eq!(
+            builder
+                .build("{x} %% 2")
+                .unwrap()
+                .eval_with_slice(&[("x", 8.0)]),
+            Ok(EmitResult::Number(5.0))
+        );
+        assert_eq!(
+            builder.build("true %% 2").unwrap().eval(),
+            Err(EvalError::TypeMismatch {
+                operator: OperatorType::Custom {
+                    symbol: "%%".to_string(),
+                    priority: 8,
+                    associativity: Associativity::Left,
+                },
+                left: VarType::Boolean,
+                right: VarType::Number,
+            })
+        );
+
+        assert!(Expression::new("4 %% 6").is_err());
+    }
+
+    #[test]
+    fn test_render_tree() {
+        let expr = Expression::new("1 + 2 * 3").unwrap();
+        assert_eq!(
+            expr.render_tree(),
+            "+\n|-- 1\n`-- *\n    |-- 2\n    `-- 3\n"
+        );
+    }
+
+    #[test]
+    fn test_allowed_operators() {
+        let builder =
+            Expression::builder().allowed_operators(HashSet::from(OperatorType::ARITHMETIC));
+        assert!(matches!(
+            builder.build("1 > 2"),
+            Err(ExpressionCreationError::OperatorNotAllowed(
+                OperatorType::GT
+            ))
+        ));
+        assert!(matches!(
+            builder.build("({x} + 1) * 2 = 3 || true"),
+            Err(ExpressionCreationError::OperatorNotAllowed(
+                OperatorType::Eq
+            ))
+        ));
+        assert_eq!(
+            builder.build("1 + 2").unwrap().eval(),
+            Ok(EmitResult::Number(3.0))
+        );
+        // functions are not restricted
+        assert!(builder.build("if({x}, 1, 2)").is_ok());
+        assert!(Expression::new("1 > 2").is_ok());
+    }
+
+    #[test]
+    fn test_as_constant() {
+        let as_constant = |expr: &str| Expression::new(expr).unwrap().as_constant();
+        assert_eq!(as_constant("2 + 3"), Some(EmitResult::Number(5.0)));
+        assert_eq!(as_constant("1 < 2"), Some(EmitResult::Boolean(true)));
+        assert_eq!(as_constant("{x} + 1"), None);
+        assert_eq!(as_constant("3.5!"), None);
+
+        // bound variables are still variables
+        let mut expr = Expression::new("{x} + 1").unwrap();
+        expr.bind_variables(&HashMap::from([("x".to_string(), 1.0)]))
+            .unwrap();
+        assert_eq!(expr.as_constant(), None);
+    }
+
+    #[test]
+    fn test_redundant_parens() {
+        fn redundant(source: &str) -> Vec<&str> {
+            Expression::redundant_parens(source)
+                .into_iter()
+                .map(|span| &source[span.start..span.end])
+                .collect()
+        }
+
+        assert_eq!(redundant("(1 + 2) * 3"), Vec::<&str>::new());
+        assert_eq!(redundant("1 + (2 * 3)"), vec!["(2 * 3)"]);
+        assert_eq!(redundant("(1) + (2 * 3)"), vec!["(1)", "(2 * 3)"]);
+        assert_eq!(redundant("((1 + 2)) * 3"), vec!["((1 + 2))"]);
+        assert_eq!(redundant("(2 - 1) - 1"), vec!["(2 - 1)"]);
+        assert_eq!(redundant("2 - (1 - 1)"), Vec::<&str>::new());
+        assert_eq!(redundant("(1 + 2)!"), Vec::<&str>::new());
+        assert_eq!(redundant("max((1), 2 * 3) + (1, 2)"), vec!["(1)"]);
+        assert_eq!(redundant("({x} > 1) && (true || false)"), vec!["({x} > 1)"]);
+        assert_eq!(redundant("1 + #"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_structurally_equal() {
+        let expr = Expression::new("1.0000001 + {x}").unwrap();
+        let other = Expression::new("1 + {x}").unwrap();
+        assert!(expr.structurally_equal(&other, 1e-5));
+        assert!(!expr.structurally_equal(&other, 1e-9));
+
+        assert!(!expr.structurally_equal(&Expression::new("1 + {y}").unwrap(), 1e-5));
+        assert!(!expr.structurally_equal(&Expression::new("1 - {x}").unwrap(), 1e-5));
+        assert!(!expr.structurally_equal(&Expression::new("1 + {x} + 0").unwrap(), 1e-5));
+        // operands of commutative operators are not reordered
+        assert!(!expr.structurally_equal(&Expression::new("{x} + 1").unwrap(), 1e-5));
+    }
+
+    #[test]
+    fn test_eval_with_expr_vars() {
+        let defs = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(name, def)| (name.to_string(), def.to_string()))
+                .collect()
+        };
+        let expr = Expression::new("{a} * 2").unwrap();
+        assert_eq!(
+            expr.eval_with_expr_vars(&defs(&[("a", "{b} + 1"), ("b", "2")]))
+                .unwrap(),
+            EmitResult::Number(6.0)
+        );
+
+        let err = expr
+            .eval_with_expr_vars(&defs(&[("a", "{b} + 1"), ("b", "{a} * 3")]))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "variables depend on themselves: a -> b -> a"
+        );
+        let err = expr
+            .eval_with_expr_vars(&defs(&[("a", "{a}")]))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "variables depend on themselves: a -> a");
+
+        // variables are resolved in order of their first occurrence
+        let cycles = defs(&[("a", "{b}"), ("b", "{a}"), ("c", "{d}"), ("d", "{c}")]);
+        let err = Expression::new("{a} + {c}")
+            .unwrap()
+            .eval_with_expr_vars(&cycles)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "variables depend on themselves: a -> b -> a"
+        );
+        let err = Expression::new("{c} + {a}")
+            .unwrap()
+            .eval_with_expr_vars(&cycles)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "variables depend on themselves: c -> d -> c"
+        );
+        let err = Expression::new("{x}")
+            .unwrap()
+            .eval_with_expr_vars(&defs(&[("x", "{y} + {z}"), ("y", "{z} +"), ("z", "{z}")]))
+            .unwrap_err();
+        assert!(matches!(err, ExprVarsError::InvalidDefinition(name, _) if name == "y"));
+
+        assert!(matches!(
+            expr.eval_with_expr_vars(&defs(&[("a", "{b} +")])),
+            Err(ExprVarsError::InvalidDefinition(name, _)) if name == "a"
+        ));
+        assert!(matches!(
+            expr.eval_with_expr_vars(&defs(&[("a", "{b} + 1")])),
+            Err(ExprVarsError::Eval(EvalError::UnboundVariable(name))) if name == "b"
+        ));
+    }
+
+    #[test]
+    fn test_eval_in_unbound_as_zero() {
+        let ctx = EvalContext {
+            unbound_variables: UnboundVariables::ZeroInArithmetic,
+            ..Default::default()
+        };
+        let expr = Expression::new("{a} + 1").unwrap();
+        assert_eq!(expr.eval_in(&ctx), Ok(EmitResult::Number(1.0)));
+
+        let expr = Expression::new("{a} && true").unwrap();
+        assert_eq!(
+            expr.eval_in(&ctx),
+            Err(EvalError::UnboundVariable("a".to_string()))
+        );
+        let expr = Expression::new("if({a}, 1, {b} * 2)").unwrap();
+        assert_eq!(
+            expr.eval_in(&ctx),
+            Err(EvalError::UnboundVariable("a".to_string()))
+        );
+        let expr = Expression::new("{a} + 2 > 1 && true").unwrap();
+        assert_eq!(expr.eval_in(&ctx), Ok(EmitResult::Boolean(true)));
+
+        // the default policy fails for any unbound variable
+        let expr = Expression::new("{a} + 1").unwrap();
+        assert_eq!(
+            expr.eval_in(&EvalContext::default()),
+            Err(EvalError::UnboundVariable("a".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_extract_constant() {
+        let mut expr = Expression::new("2 * {x} + 2").unwrap();
+        expr.extract_constant(2.0, "two");
+        assert_eq!(
+            expr.clone().into_rpn(),
+            vec![
+                Value::Variable("two".to_string()),
+                Value::Variable("x".to_string()),
+                Value::Operator(OperatorType::Times),
+                Value::Variable("two".to_string()),
+                Value::Operator(OperatorType::Plus),
+            ]
+        );
+        assert_eq!(
+            expr.eval_with_slice(&[("two", 3.0), ("x", 1.0)]),
+            Ok(EmitResult::Number(6.0))
+        );
+
+        // literals are matched by their f32 value
+        let mut expr = Expression::new("0.30000001 + 0.3 + 0.1").unwrap();
+        expr.extract_constant(0.3, "x");
+        assert_eq!(
+            expr.variable_counts(),
+            HashMap::from([("x".to_string(), 2)])
+        );
+    }
+
+    #[test]
+    fn test_effective_variables() {
+        let expr = Expression::new("{a} > 1 && {b} < 2").unwrap();
+        assert_eq!(
+            expr.effective_variables(&HashMap::from([("a".to_string(), 0.0)])),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            expr.effective_variables(&HashMap::from([("a".to_string(), 5.0)])),
+            vec!["b".to_string()]
+        );
+        assert_eq!(
+            expr.effective_variables(&HashMap::new()),
+            vec!["a".to_string(), "b".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_between() {
+        let eval = |expr: &str| Expression::new(expr).unwrap().eval();
+
+        assert_eq!(eval("between(5, 1, 10)"), Ok(EmitResult::Boolean(true)));
+        assert_eq!(eval("between(0, 1, 10)"), Ok(EmitResult::Boolean(false)));
+        assert_eq!(eval("between(1, 1, 10)"), Ok(EmitResult::Boolean(true)));
+        assert_eq!(eval("between(10, 1, 10)"), Ok(EmitResult::Boolean(true)));
+        assert_eq!(
+            Expression::new("between({x}, 1, 10) && true")
+                .unwrap()
+                .check_with_var_types(&HashMap::from([("x".to_string(), VarType::Number)])),
+            Ok(VarType::Boolean)
+        );
+    }
+
+    #[test]
+    fn test_first_true() {
+        let eval = |expr: &str| Expression::new(expr).unwrap().eval();
+
+        assert_eq!(
+            eval("first_true(false, true, false)"),
+            Ok(EmitResult::Number(2.0))
+        );
+        assert_eq!(
+            eval("first_true(false, false)"),
+            Ok(EmitResult::Number(0.0))
+        );
+        assert_eq!(
+            Expression::new("first_true({x} < 0, {x} < 10, true)")
+                .unwrap()
+                .eval_with_slice(&[("x", 5.0)]),
+            Ok(EmitResult::Number(2.0))
+        );
+        assert_eq!(
+            eval("first_true(false, 1)"),
+            Err(EvalError::ArgumentTypeMismatch {
+                function: FunctionType::FirstTrue,
+                expected: VarType::Boolean,
+                found: VarType::Number,
+            })
+        );
+        assert_eq!(
+            Expression::new("first_true({x} > 1, false) + 1")
+                .unwrap()
+                .check_with_var_types(&HashMap::from([("x".to_string(), VarType::Number)])),
+            Ok(VarType::Number)
+        );
+    }
+
+    #[test]
+    fn test_list() {
+        assert_eq!(
+            Expression::new("(1, 2, 3)").unwrap().eval(),
+            Ok(EmitResult::List(vec![1.0, 2.0, 3.0]))
+        );
+        assert_eq!(
+            Expression::new("({x} * 2, max({x}, 5))")
+                .unwrap()
+                .eval_with_slice(&[("x", 4.0)]),
+            Ok(EmitResult::List(vec![8.0, 5.0]))
+        );
+        // arithmetic on lists is not supported
+        assert_eq!(
+            Expression::new("(1, 2) + 1").unwrap().eval(),
+            Err(EvalError::TypeMismatch {
+                operator: OperatorType::Plus,
+                left: VarType::List,
+                right: VarType::Number,
+            })
+        );
+        assert_eq!(
+            Expression::new("(1, 2)")
+                .unwrap()
+                .check_with_var_types(&HashMap::new()),
+            Ok(VarType::List)
+        );
+    }
+
+    #[test]
+    fn test_pow() {
+        let eval = |expr: &str| Expression::new(expr).unwrap().eval_number();
+
+        assert_eq!(eval("pow(2, 10)"), Ok(1024.0));
+        assert_eq!(eval("pow(9, 0.5)"), Ok(3.0));
+        assert_eq!(eval("pow(2, pow(2, 3))"), Ok(256.0));
+        assert_eq!(eval("pow(2, 3) * 2 + 1"), Ok(17.0));
+    }
+
+    #[test]
+    fn test_eval_clamped() {
+        let expr = Expression::new("{x} * 10").unwrap();
+        let eval = |x: f32| {
+            expr.with_bindings(&HashMap::from([("x".to_string(), x)]))
+                .unwrap()
+                .eval_clamped(0.0, 50.0)
+        };
+
+        assert_eq!(eval(-1.0), Ok(0.0));
+        assert_eq!(eval(2.5), Ok(25.0));
+        assert_eq!(eval(9.0), Ok(50.0));
+        assert_eq!(eval(f32::INFINITY), Ok(50.0));
+        assert_eq!(eval(f32::NAN), Err(EvalError::NaNResult));
+
+        assert_eq!(
+            expr.eval_clamped(50.0, 0.0),
+            Err(EvalError::InvalidBounds {
+                min: 50.0,
+                max: 0.0
+            })
+        );
+        assert!(matches!(
+            expr.eval_clamped(f32::NAN, 1.0),
+            Err(EvalError::InvalidBounds { min, max: 1.0 }) if min.is_nan()
+        ));
+        assert!(matches!(
+            expr.eval_clamped(0.0, f32::NAN),
+            Err(EvalError::InvalidBounds { min: 0.0, max }) if max.is_nan()
+        ));
+        assert_eq!(
+            Expression::new("3").unwrap().eval_clamped(1.0, 1.0),
+            Ok(1.0)
+        );
+
+        assert_eq!(
+            Expression::new("1 < 2").unwrap().eval_clamped(0.0, 1.0),
+            Err(EvalError::WrongResultType {
+                expected: VarType::Number,
+                found: VarType::Boolean,
+            })
+        );
+    }
+
+    #[test]
+    fn test_angle_mode() {
+        let mut expr = Expression::new("sin(90)").unwrap();
+        assert!((expr.eval_number().unwrap() - 0.894).abs() < 0.001);
+
+        expr.set_angle_mode(AngleMode::Degrees);
+        assert!((expr.eval_number().unwrap() - 1.0).abs() < 1e-6);
+
+        let mut expr = Expression::new("cos(180) + tan(45)").unwrap();
+        expr.set_angle_mode(AngleMode::Degrees);
+        assert!(expr.eval_number().unwrap().abs() < 1e-6);
+
+        // only the angle argument is converted
+        let mut expr = Expression::new("sqrt(pow(sin(30), 2))").unwrap();
+        expr.set_angle_mode(AngleMode::Degrees);
+        assert!((expr.eval_number().unwrap() - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_typed_expression() {
+        let expr: TypedExpression<f32> = Expression::new("1 + 2").unwrap().typed_number().unwrap();
+        assert_eq!(expr.eval(), Ok(3.0));
+
+        assert_eq!(
+            Expression::new("1 > 2").unwrap().typed_number().err(),
+            Some(TypeError::WrongResultType {
+                expected: VarType::Number,
+                found: VarType::Boolean,
+            })
+        );
+
+        let mut expr = Expression::new("{x} > 2").unwrap().typed_boolean().unwrap();
+        expr.bind_variables(&HashMap::from([("x".to_string(), 3.0)]))
+            .unwrap();
+        assert_eq!(expr.eval(), Ok(true));
+        assert_eq!(expr.eval_with(|_| Some(1.0)), Ok(false));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_typed_expression_boolean_variable() {
+        let mut expr = Expression::new("if(true, {x}, 1)").unwrap();
+        expr.bind_json(&serde_json::json!({"x": true})).unwrap();
+
+        let expr = expr.typed_number().unwrap();
+        assert_eq!(
+            expr.eval(),
+            Err(EvalError::WrongResultType {
+                expected: VarType::Number,
+                found: VarType::Boolean,
+            })
+        );
+    }
+
+    #[test]
+    fn test_sweep() {
+        let expr = Expression::new("{x} * {x}").unwrap();
+        assert_eq!(
+            expr.sweep("x", 0.0, 2.0, 3, &HashMap::new()),
+            Ok(vec![
+                (0.0, EmitResult::Number(0.0)),
+                (1.0, EmitResult::Number(1.0)),
+                (2.0, EmitResult::Number(4.0)),
+            ])
+        );
+        assert_eq!(expr.sweep("x", 0.0, 2.0, 0, &HashMap::new()), Ok(vec![]));
+
+        let expr = Expression::new("{x} + {y}").unwrap();
+        let fixed = HashMap::from([("y".to_string(), 10.0)]);
+        assert_eq!(
+            expr.sweep("x", 1.0, 1.5, 2, &fixed),
+            Ok(vec![
+                (1.0, EmitResult::Number(11.0)),
+                (1.5, EmitResult::Number(11.5)),
+            ])
+        );
+        assert_eq!(
+            expr.sweep("x", 0.0, 1.0, 2, &HashMap::new()),
+            Err(EvalError::UnboundVariable("y".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_compare() {
+        let eval = |expr: &str| Expression::new(expr).unwrap().eval_number().unwrap();
+
+        assert_eq!(eval("compare(1, 2)"), -1.0);
+        assert_eq!(eval("compare(2, 2)"), 0.0);
+        assert_eq!(eval("compare(3, 1)"), 1.0);
+        assert_eq!(eval("compare(0 / 0, 1)"), 0.0);
+    }
+
+    #[test]
+    fn test_debug_pipeline() {
+        let (tokens, rpn) = Expression::debug_pipeline("1 + 2 * 3").unwrap();
+        assert_eq!(describe_tokens(&tokens), "NUM(1) OP(+) NUM(2) OP(*) NUM(3)");
+        assert_eq!(
+            rpn,
+            vec![
+                Value::Number(1.0),
+                Value::Number(2.0),
+                Value::Number(3.0),
+                Value::Operator(OperatorType::Times),
+                Value::Operator(OperatorType::Plus),
+            ]
+        );
+
+        assert!(matches!(
+            Expression::debug_pipeline("1 +"),
+            Err(ExpressionCreationError::ParserError(_))
+        ));
+    }
+
+    #[test]
+    fn test_scientific_constants() {
+        let builder = Expression::builder().scientific_constants(true);
+        let eval = |expr: &str| builder.build(expr).unwrap().eval_number().unwrap();
+
+        assert_eq!(eval("tau"), 2.0 * std::f32::consts::PI);
+        assert!((eval("phi") - 1.618).abs() < 1e-3);
+        assert!((eval("sqrt2") - 1.414).abs() < 1e-3);
+        assert_eq!(eval("2 * pi"), eval("tau"));
+        assert_eq!(eval("inf"), f32::INFINITY);
+
+        // defined constants take precedence
+        let builder = builder.constants(HashMap::from([("e".to_string(), 3.0)]));
+        assert_eq!(builder.build("e").unwrap().eval_number(), Ok(3.0));
+
+        assert!(matches!(
+            Expression::new("tau"),
+            Err(ExpressionCreationError::ParserError(
+                ParserError::UnknownIdentifier(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_implicit_multiplication() {
+        let builder = Expression::builder().implicit_multiplication(true);
+        let expr = builder.build("2{x} + 3(1 + 1)").unwrap();
+        assert_eq!(
+            expr.eval_with_slice(&[("x", 4.0)]),
+            Ok(EmitResult::Number(14.0))
+        );
+
+        assert!(matches!(
+            Expression::new("2{x}"),
+            Err(ExpressionCreationError::ParserError(
+                ParserError::MissingOperator(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_rounding() {
+        let eval = |expr: &str| Expression::new(expr).unwrap().eval_number().unwrap();
+
+        assert_eq!(eval("round_half_even(2.5)"), 2.0);
+        assert_eq!(eval("round_half_even(3.5)"), 4.0);
+        assert_eq!(eval("round_half_even(2.6)"), 3.0);
+        assert_eq!(eval("round(2.5)"), 3.0);
+        assert_eq!(eval("round(2.4)"), 2.0);
+    }
+
+    #[test]
+    fn test_simplify_boolean() {
+        for (expr, simplified) in [
+            ("true && {x} > 0", "{x} > 0"),
+            ("false || {x} > 0", "{x} > 0"),
+            ("true || {x} > 0", "true"),
+            ("{x} < 1 && false", "false"),
+        ] {
+            let original = Expression::new(expr).unwrap();
+            let mut expr = original.clone();
+            expr.simplify_boolean();
+            assert_eq!(
+                expr.clone().into_rpn(),
+                Expression::new(simplified).unwrap().into_rpn()
+            );
+
+            for x in [-1.0, 0.5, 2.0] {
+                let vars = HashMap::from([("x".to_string(), x)]);
+                assert_eq!(
+                    expr.with_bindings(&vars).unwrap().eval(),
+                    original.with_bindings(&vars).unwrap().eval()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_derivative() {
+        let eval_at = |expr: &Expression, x: f32| {
+            expr.with_bindings(&HashMap::from([
+                ("x".to_string(), x),
+                ("y".to_string(), 5.0),
+            ]))
+            .unwrap()
+            .eval_number()
+            .unwrap()
+        };
+
+        let square = Expression::new("{x} * {x}")
+            .unwrap()
+            .derivative("x")
+            .unwrap();
+        for x in [-3.0, -0.5, 0.0, 1.0, 2.5, 10.0] {
+            assert_eq!(eval_at(&square, x), 2.0 * x);
+        }
+
+        let polynomial = Expression::new("pow({x}, 3) - 4 * {x} * {y} + 7")
+            .unwrap()
+            .derivative("x")
+            .unwrap();
+        for x in [-2.0, 0.0, 1.0, 3.0] {
+            assert_eq!(eval_at(&polynomial, x), 3.0 * x * x - 20.0);
+        }
+
+        let reciprocal = Expression::new("1 / {x}").unwrap().derivative("x").unwrap();
+        for x in [-2.0, 0.5, 4.0] {
+            assert_eq!(eval_at(&reciprocal, x), -1.0 / (x * x));
+        }
+
+        assert!(matches!(
+            Expression::new("sqrt({x})").unwrap().derivative("x"),
+            Err(DerivativeError::UnsupportedFunction(FunctionType::Sqrt))
+        ));
+    }
+
+    #[test]
+    fn test_in() {
+        let eval = |expr: &str| Expression::new(expr).unwrap().eval();
+
+        assert_eq!(eval("2 in (1, 2, 3)"), Ok(EmitResult::Boolean(true)));
+        assert_eq!(eval("4 in (1, 2, 3)"), Ok(EmitResult::Boolean(false)));
+        assert_eq!(
+            Expression::new("{status} in (1, 2) || {status} > 10")
+                .unwrap()
+                .eval_with_slice(&[("status", 2.0)]),
+            Ok(EmitResult::Boolean(true))
+        );
+        assert_eq!(
+            eval("2 in 2"),
+            Err(EvalError::TypeMismatch {
+                operator: OperatorType::In,
+                left: VarType::Number,
+                right: VarType::Number,
+            })
+        );
+        assert_eq!(
+            Expression::new("{x} in (1, 2)")
+                .unwrap()
+                .check_with_var_types(&HashMap::from([("x".to_string(), VarType::Number)])),
+            Ok(VarType::Boolean)
+        );
+    }
+
+    #[test]
+    fn test_unknown_function() {
+        assert!(matches!(
+            Expression::new("frobnicate(1)"),
+            Err(ExpressionCreationError::UnknownFunction(name)) if name == "frobnicate"
+        ));
+
+        let mut functions = FunctionRegistry::new();
+        functions.register("frobnicate", 1, |args| args[0] * 2.0);
+        let expr = Expression::builder()
+            .functions(functions)
+            .build("frobnicate(1)")
+            .unwrap();
+        assert_eq!(expr.eval_number(), Ok(2.0));
+    }
+
+    #[test]
+    fn test_eval_resolver() {
+        struct Point {
+            a: f32,
+            b: f32,
+        }
+
+        impl VariableResolver for Point {
+            fn resolve(&self, name: &str) -> Option<f32> {
+                match name {
+                    "a" => Some(self.a),
+                    "b" => Some(self.b),
+                    _ => None,
+                }
+            }
+        }
+
+        let point = Point { a: 1.0, b: 2.5 };
+        assert_eq!(
+            Expression::new("{a} + {b}").unwrap().eval_resolver(&point),
+            Ok(EmitResult::Number(3.5))
+        );
+        assert_eq!(
+            Expression::new("{a} + {c}").unwrap().eval_resolver(&point),
+            Err(EvalError::UnboundVariable("c".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_reported() {
+        let source = "1 + @";
+        let err = Expression::new(source).unwrap_err();
+        assert_eq!(
+            err.reported(source),
+            "error: token not matched at the beginning of substring: '@'\n  |\n1 | 1 + @\n  |     ^"
+        );
+
+        let source = "1 +";
+        let err = Expression::new(source).unwrap_err();
+        assert_eq!(
+            err.reported(source),
+            "error: operator `+` is missing an operand\n  |\n  | 1 +"
+        );
+    }
+
+    #[test]
+    fn test_macros() {
+        let builder = Expression::builder()
+            .define_macro("dist", "sqrt({x}*{x} + {y}*{y})")
+            .unwrap()
+            .define_macro("far", "dist > 5")
+            .unwrap();
+        let expr = builder.build("dist > 5").unwrap();
+
+        assert_eq!(
+            expr.eval_with_slice(&[("x", 3.0), ("y", 4.0)]),
+            Ok(EmitResult::Boolean(false))
+        );
+        assert_eq!(
+            expr.eval_with_slice(&[("x", 6.0), ("y", 0.0)]),
+            Ok(EmitResult::Boolean(true))
+        );
+        assert_eq!(
+            builder
+                .build("far || {x} < 0")
+                .unwrap()
+                .eval_with_slice(&[("x", 6.0), ("y", 8.0)]),
+            Ok(EmitResult::Boolean(true))
+        );
+        assert!(Expression::builder().define_macro("bad", "1 +").is_err());
+    }
+
+    #[test]
+    fn test_logarithms() {
+        let eval = |expr: &str| Expression::new(expr).unwrap().eval_number().unwrap();
+
+        assert_eq!(eval("log10(1000)"), 3.0);
+        assert_eq!(eval("log2(8)"), 3.0);
+        assert_eq!(eval("log(2, 8)"), 3.0);
+        assert!((eval("log(100)") - 100f32.ln()).abs() < 1e-6);
+        assert_eq!(eval("log(0)"), f32::NEG_INFINITY);
+        assert!(eval("log10(0 - 1)").is_nan());
+
+        let mut expr = Expression::new("log2(0 - 1)").unwrap();
+        expr.set_strict_nan(true);
+        assert_eq!(expr.eval_number(), Err(EvalError::InvalidLogarithm(-1.0)));
+
+        assert!(matches!(
+            Expression::new("log(1, 2, 3)"),
+            Err(ExpressionCreationError::ParserError(
+                ParserError::InvalidArgumentCount { found: 3, .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_structure() {
+        assert!(matches!(
+            Expression::new(""),
+            Err(ExpressionCreationError::InvalidStructure)
+        ));
+        assert!(matches!(
+            Expression::new("()"),
+            Err(ExpressionCreationError::ParserError(
+                ParserError::EmptyParentheses
+            ))
+        ));
+        assert!(Expression::new("(1)").is_ok());
+    }
+
+    proptest! {
+        #[test]
+        fn test_created_expressions_evaluate(input in r"[0-9{}xy()+\-*/&|<>=!?, ]{0,20}|(abs|min|if|between)\([0-9{}xy+<, ]{0,12}\)") {
+            if let Ok(expr) = Expression::new(&input) {
+                let result = expr.eval_with(|_| Some(1.0));
+                prop_assert!(
+                    !matches!(result, Err(EvalError::NotEnoughValues | EvalError::TooMuchValues)),
+                    "{input:?} evaluated to {result:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_eval_each() {
+        let expr = Expression::new("{x} * 2").unwrap();
+        let maps = [
+            HashMap::from([("x".to_string(), 1.0)]),
+            HashMap::from([("x".to_string(), 2.5)]),
+            HashMap::from([("y".to_string(), 3.0)]),
+        ];
+
+        assert_eq!(
+            expr.eval_each(maps.iter()).collect::<Vec<_>>(),
+            vec![
+                Ok(EmitResult::Number(2.0)),
+                Ok(EmitResult::Number(5.0)),
+                Err(EvalError::UnboundVariable("x".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reject_non_finite() {
+        let strict = Expression::builder().reject_non_finite(true);
+
+        assert!(matches!(
+            strict.build("1 + 0/0"),
+            Err(ExpressionCreationError::AlwaysNaN)
+        ));
+        assert!(matches!(
+            strict.build("{x} > log(0)"),
+            Err(ExpressionCreationError::AlwaysNaN)
+        ));
+        assert!(strict.build("{x} / 0").is_ok());
+        assert!(Expression::new("1 + 0/0").is_ok());
+    }
+
+    #[test]
+    fn test_reject_fragile_comparisons() {
+        let strict = Expression::builder().reject_fragile_comparisons(true);
+
+        assert!(matches!(
+            strict.build("1/3 = 0.333"),
+            Err(ExpressionCreationError::FragileComparison)
+        ));
+        assert!(matches!(
+            strict.build("{x} > 0 && {y} == {x} / 2"),
+            Err(ExpressionCreationError::FragileComparison)
+        ));
+        assert!(strict.build("1/3 < 0.334").is_ok());
+        assert!(strict.build("{x} * 2 = 4").is_ok());
+        assert!(Expression::new("1/3 = 0.333").is_ok());
+    }
+
+    #[test]
+    fn test_compile() {
+        let mut expr = Expression::new("{x} * {y}").unwrap();
+        let eval = expr.compile();
+        expr.bind_variables(&HashMap::from([
+            ("x".to_string(), 0.0),
+            ("y".to_string(), 0.0),
+        ]))
+        .unwrap();
+
+        for (x, y) in [(1.0, 2.0), (3.0, 4.0), (-1.5, 2.0)] {
+            let vars = HashMap::from([("x".to_string(), x), ("y".to_string(), y)]);
+            assert_eq!(eval(&vars), Ok(EmitResult::Number(x * y)));
+        }
+        assert_eq!(
+            eval(&HashMap::new()),
+            Err(EvalError::UnboundVariable("x".to_string()))
+        );
+        // values of the previous call are not kept
+        assert_eq!(
+            eval(&HashMap::from([("x".to_string(), 2.0)])),
+            Err(EvalError::UnboundVariable("y".to_string()))
+        );
+
+        let eval = Expression::new("{x} ?? 1 + {y}").unwrap().compile();
+        assert_eq!(
+            eval(&HashMap::from([("y".to_string(), 2.0)])),
+            Ok(EmitResult::Number(3.0))
+        );
+        assert_eq!(
+            eval(&HashMap::from([
+                ("x".to_string(), 5.0),
+                ("y".to_string(), 2.0)
+            ])),
+            Ok(EmitResult::Number(5.0))
+        );
+    }
+
+    #[test]
+    fn test_partial_cmp_value() {
+        use std::cmp::Ordering;
+
+        let cmp = |a: &str, b: &str| {
+            Expression::new(a)
+                .unwrap()
+                .partial_cmp_value(&Expression::new(b).unwrap())
+        };
+
+        assert_eq!(cmp("1+1", "3"), Some(Ordering::Less));
+        assert_eq!(cmp("3", "1+1"), Some(Ordering::Greater));
+        assert_eq!(cmp("4 / 2", "1+1"), Some(Ordering::Equal));
+        assert_eq!(cmp("1 < 2", "3"), None);
+        assert_eq!(cmp("{x}", "3"), None);
+        assert_eq!(cmp("0 / 0", "3"), None);
     }
 }
@@ -0,0 +1,236 @@
+use crate::{
+    numeric::{Num, Overflow},
+    parser::OperatorType,
+};
+use std::{
+    cmp::Ordering,
+    ops::{Add, Div, Mul, Sub},
+    str::FromStr,
+};
+
+/// Exact rational number in lowest terms with a positive denominator,
+/// for evaluating divisions without rounding, e.g. with `NumEmitter::<Fraction>`.
+/// Arithmetic fails with [`crate::EvalError::Overflow`] if a result does not fit `i64`
+/// or on division by zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fraction {
+    num: i64,
+    den: i64,
+}
+
+impl Fraction {
+    /// Reduces the fraction to lowest terms, returns `None` if `den` is zero.
+    pub fn new(num: i64, den: i64) -> Option<Self> {
+        Self::reduced(num as i128, den as i128)
+    }
+
+    pub fn num(&self) -> i64 {
+        self.num
+    }
+
+    pub fn den(&self) -> i64 {
+        self.den
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.den == 1
+    }
+
+    // `None` for a zero denominator or if the reduced fraction does not fit `i64`
+    fn reduced(num: i128, den: i128) -> Option<Self> {
+        if den == 0 {
+            return None;
+        }
+        let divisor = gcd(num, den) * den.signum();
+        Some(Self {
+            num: (num / divisor).try_into().ok()?,
+            den: (den / divisor).try_into().ok()?,
+        })
+    }
+
+    fn checked(self, op: &OperatorType, other: Self) -> Option<Self> {
+        let (a, b) = (self.num as i128, self.den as i128);
+        let (c, d) = (other.num as i128, other.den as i128);
+        match op {
+            OperatorType::Plus => Self::reduced(a * d + c * b, b * d),
+            OperatorType::Minus => Self::reduced(a * d - c * b, b * d),
+            OperatorType::Times => Self::reduced(a * c, b * d),
+            OperatorType::Divide => Self::reduced(a * d, b * c),
+            _ => panic!("operator `{op}` is not arithmetic"),
+        }
+    }
+}
+
+// greatest common divisor, positive unless both are zero
+fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+impl From<i64> for Fraction {
+    fn from(value: i64) -> Self {
+        Self { num: value, den: 1 }
+    }
+}
+
+/// `3` for integers, `1/2` otherwise
+impl std::fmt::Display for Fraction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_integer() {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParseFractionError(String);
+
+impl std::fmt::Display for ParseFractionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid fraction: '{}'", self.0)
+    }
+}
+
+/// Parses integers, decimals, e.g. `0.25` is `1/4`, and fractions such as `1/4`.
+impl FromStr for Fraction {
+    type Err = ParseFractionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseFractionError(s.to_owned());
+        if let Some((num, den)) = s.split_once('/') {
+            let num = num.parse().map_err(|_| err())?;
+            let den = den.parse().map_err(|_| err())?;
+            return Self::new(num, den).ok_or_else(err);
+        }
+
+        let (whole, decimals) = s.split_once('.').unwrap_or((s, ""));
+        if !decimals.chars().all(|c| c.is_ascii_digit()) {
+            return Err(err());
+        }
+        let num: i128 = format!("{whole}{decimals}").parse().map_err(|_| err())?;
+        let den = 10_i128.checked_pow(decimals.len() as u32).ok_or_else(err)?;
+        Self::reduced(num, den).ok_or_else(err)
+    }
+}
+
+impl PartialOrd for Fraction {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Fraction {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // denominators are positive
+        (self.num as i128 * other.den as i128).cmp(&(other.num as i128 * self.den as i128))
+    }
+}
+
+impl Num for Fraction {
+    fn arithmetic(self, op: &OperatorType, other: Self, overflow: Overflow) -> Option<Self> {
+        let _ = overflow;
+        self.checked(op, other)
+    }
+}
+
+impl Add for Fraction {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        self.checked(&OperatorType::Plus, other)
+            .expect("fraction addition overflowed")
+    }
+}
+
+impl Sub for Fraction {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        self.checked(&OperatorType::Minus, other)
+            .expect("fraction subtraction overflowed")
+    }
+}
+
+impl Mul for Fraction {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self {
+        self.checked(&OperatorType::Times, other)
+            .expect("fraction multiplication overflowed")
+    }
+}
+
+impl Div for Fraction {
+    type Output = Self;
+    fn div(self, other: Self) -> Self {
+        self.checked(&OperatorType::Divide, other)
+            .expect("fraction division by zero or overflow")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Fraction;
+    use crate::{
+        emitter::{EmitResult, EvalError},
+        numeric::NumEmitter,
+        parser::OperatorType,
+    };
+    use pretty_assertions::assert_eq;
+
+    fn fraction(num: i64, den: i64) -> Fraction {
+        Fraction::new(num, den).unwrap()
+    }
+
+    #[test]
+    fn test_new() {
+        assert_eq!(fraction(2, 4), fraction(1, 2));
+        assert_eq!(fraction(3, -6), fraction(-1, 2));
+        assert_eq!(fraction(-3, -6).to_string(), "1/2");
+        assert_eq!(fraction(0, -5), Fraction::from(0));
+        assert_eq!(fraction(6, 3).to_string(), "2");
+        assert_eq!(Fraction::new(1, 0), None);
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("3".parse(), Ok(Fraction::from(3)));
+        assert_eq!("0.25".parse(), Ok(fraction(1, 4)));
+        assert_eq!("-1.5".parse(), Ok(fraction(-3, 2)));
+        assert_eq!("2/6".parse(), Ok(fraction(1, 3)));
+        assert!("1/0".parse::<Fraction>().is_err());
+        assert!("1e5".parse::<Fraction>().is_err());
+    }
+
+    #[test]
+    fn test_eval() {
+        let eval = |expr: &str| {
+            NumEmitter::<Fraction>::parse(expr)
+                .unwrap()
+                .eval_with(|_| None)
+        };
+
+        assert_eq!(eval("1/3 + 1/6"), Ok(EmitResult::Number(fraction(1, 2))));
+        assert_eq!(eval("2/4"), Ok(EmitResult::Number(fraction(1, 2))));
+        assert_eq!(
+            eval("0.1 * 3 - 1/10"),
+            Ok(EmitResult::Number(fraction(1, 5)))
+        );
+        assert_eq!(eval("1/3 * 3 = 1"), Ok(EmitResult::Boolean(true)));
+        assert_eq!(eval("1/3 < 0.3334"), Ok(EmitResult::Boolean(true)));
+        assert_eq!(
+            eval("max(2/3, 3/5)"),
+            Ok(EmitResult::Number(fraction(2, 3)))
+        );
+        assert_eq!(
+            eval("1 / 0"),
+            Err(EvalError::Overflow(OperatorType::Divide))
+        );
+        assert_eq!(
+            eval("9223372036854775807 + 1"),
+            Err(EvalError::Overflow(OperatorType::Plus))
+        );
+    }
+}
@@ -1,9 +1,51 @@
+//! Parsing and evaluation of logical and mathematical string expressions.
+//!
+//! Most users only need [`Expression`]. The lower-level pipeline is exposed as well,
+//! so the tokens and the reverse polish notation can be inspected separately:
+//!
+//! ```
+//! use rust_exprs::{tokens_to_rpn, OperatorType, TokenType, Tokenizer, Value};
+//!
+//! let tokens = Tokenizer::new().tokenize("1 + {x}").unwrap();
+//! assert_eq!(tokens[1].token_type, TokenType::Operator);
+//!
+//! let rpn = tokens_to_rpn(tokens).unwrap();
+//! assert_eq!(
+//!     rpn,
+//!     vec![
+//!         Value::Number(1.0),
+//!         Value::Variable("x".to_string()),
+//!         Value::Operator(OperatorType::Plus),
+//!     ]
+//! );
+//! ```
+
+mod ast;
+mod context;
 mod emitter;
 pub mod expression;
-mod parser;
-mod tokenizer;
+mod fraction;
+mod numeric;
+pub mod parser;
+pub mod tokenizer;
+mod type_checker;
 
-pub use emitter::{BindVariablesError, EmitResult, EvalError, VariableMap};
+pub use ast::{Ast, DerivativeError};
+pub use context::{
+    Associativity, CustomFunction, CustomOperator, EvalContext, FunctionRegistry, OperatorTable,
+    UnboundVariables, VariableResolver,
+};
+pub use emitter::{
+    AngleMode, BindVariablesError, EmitResult, EvalError, EvalOptions, OperatorEval, VariableMap,
+};
 pub use expression::Expression;
-pub use parser::ParserError;
-pub use tokenizer::TokenizerError;
+pub use fraction::{Fraction, ParseFractionError};
+pub use numeric::{Num, NumEmitter, Overflow};
+pub use parser::{
+    tokens_to_rpn, FunctionType, OperatorType, Parser, ParserError, Rpn, Value,
+    SCIENTIFIC_CONSTANTS,
+};
+pub use tokenizer::{
+    describe_tokens, Span, Token, TokenList, TokenType, Tokenizer, TokenizerError,
+};
+pub use type_checker::{ResultType, TypeError, VarType};
@@ -0,0 +1,478 @@
+use crate::{
+    emitter::{coalesce_targets, EmitResult, EvalError},
+    expression::ExpressionCreationError,
+    parser::{FunctionType, OperatorType, Parser, Rpn, Value},
+    tokenizer::Tokenizer,
+    type_checker::VarType,
+};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    ops::{Add, Div, Mul, Sub},
+    str::FromStr,
+};
+
+/// Behavior of integer arithmetic whose result does not fit the type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// fails with [`EvalError::Overflow`]
+    #[default]
+    Checked,
+    Wrapping,
+    Saturating,
+}
+
+/// Number type expressions can be evaluated with, e.g. `impl Num for Decimal {}`.
+pub trait Num:
+    Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + PartialOrd
+    + FromStr
+    + Clone
+    + Debug
+{
+    /// Evaluates `+`, `-`, `*` or `/`, returns `None` if the result overflows
+    /// in [`Overflow::Checked`] mode or is undefined, e.g. integer division by zero.
+    /// Uses the operators and ignores `overflow` by default.
+    fn arithmetic(self, op: &OperatorType, other: Self, overflow: Overflow) -> Option<Self> {
+        let _ = overflow;
+        match op {
+            OperatorType::Plus => Some(self + other),
+            OperatorType::Minus => Some(self - other),
+            OperatorType::Times => Some(self * other),
+            OperatorType::Divide => Some(self / other),
+            _ => panic!("operator `{op}` is not arithmetic"),
+        }
+    }
+}
+
+impl Num for f32 {}
+impl Num for f64 {}
+
+macro_rules! impl_integer_num {
+    ($($int:ty),*) => {$(
+        impl Num for $int {
+            fn arithmetic(self, op: &OperatorType, other: Self, overflow: Overflow) -> Option<Self> {
+                // division by zero is undefined in every mode
+                match (op, overflow) {
+                    (OperatorType::Plus, Overflow::Checked) => self.checked_add(other),
+                    (OperatorType::Plus, Overflow::Wrapping) => Some(self.wrapping_add(other)),
+                    (OperatorType::Plus, Overflow::Saturating) => Some(self.saturating_add(other)),
+                    (OperatorType::Minus, Overflow::Checked) => self.checked_sub(other),
+                    (OperatorType::Minus, Overflow::Wrapping) => Some(self.wrapping_sub(other)),
+                    (OperatorType::Minus, Overflow::Saturating) => Some(self.saturating_sub(other)),
+                    (OperatorType::Times, Overflow::Checked) => self.checked_mul(other),
+                    (OperatorType::Times, Overflow::Wrapping) => Some(self.wrapping_mul(other)),
+                    (OperatorType::Times, Overflow::Saturating) => Some(self.saturating_mul(other)),
+                    (OperatorType::Divide, Overflow::Checked) => self.checked_div(other),
+                    (OperatorType::Divide, _) if other == 0 => None,
+                    (OperatorType::Divide, Overflow::Wrapping) => Some(self.wrapping_div(other)),
+                    (OperatorType::Divide, Overflow::Saturating) => Some(self.saturating_div(other)),
+                    _ => panic!("operator `{op}` is not arithmetic"),
+                }
+            }
+        }
+    )*};
+}
+
+impl_integer_num!(i32, i64);
+
+/// Evaluates expressions with numbers of type `N`, e.g. a decimal or fixed-point type.
+/// Supports arithmetic, comparison and logical operators, `??`, lists and the `if`, `min`,
+/// `max` and `compare` functions. Other operators and functions need `f32`,
+/// see [`crate::Emitter`].
+#[derive(Debug, Clone)]
+pub struct NumEmitter<N> {
+    rpn: Rpn<N>,
+    // rpn index of a variable which is the left operand of `??`, and the index of the `??`
+    coalesce_targets: HashMap<usize, usize>,
+    overflow: Overflow,
+}
+
+impl<N: Num> NumEmitter<N> {
+    pub fn new(rpn: Rpn<N>) -> Self {
+        Self {
+            coalesce_targets: coalesce_targets(&rpn),
+            rpn,
+            overflow: Overflow::default(),
+        }
+    }
+
+    /// Parses the expression with the default tokenizer and parser, number literals are
+    /// parsed directly as `N`.
+    pub fn parse(expr: &str) -> Result<Self, ExpressionCreationError> {
+        let tokens = Tokenizer::new().tokenize(expr)?;
+        Ok(Self::new(Parser::new().parse_as(tokens)?))
+    }
+
+    pub fn rpn(&self) -> &Rpn<N> {
+        &self.rpn
+    }
+
+    /// see [`Overflow`], only used by integer types
+    pub fn set_overflow(&mut self, overflow: Overflow) {
+        self.overflow = overflow;
+    }
+
+    /// Evaluates the expression with variable values looked up by name.
+    pub fn eval_with<F>(&self, lookup: F) -> Result<EmitResult<N>, EvalError>
+    where
+        F: Fn(&str) -> Option<N>,
+    {
+        // stack - last index in, last index out
+        let mut value_stack: Vec<EmitResult<N>> = vec![];
+        // `??` operators whose left operand is missing, the right operand is their result
+        let mut missing_left: Vec<usize> = vec![];
+
+        for (index, value) in self.rpn.iter().enumerate() {
+            match value {
+                Value::Number(num) => value_stack.push(EmitResult::Number(num.clone())),
+                Value::Boolean(boolean) => value_stack.push(EmitResult::Boolean(*boolean)),
+                Value::Placeholder(index) => return Err(EvalError::UnfilledPlaceholder(*index)),
+                Value::Variable(name) => match (lookup(name), self.coalesce_targets.get(&index)) {
+                    (Some(num), _) => value_stack.push(EmitResult::Number(num)),
+                    (None, Some(coalesce_index)) => missing_left.push(*coalesce_index),
+                    (None, None) => return Err(EvalError::UnboundVariable(name.to_owned())),
+                },
+                Value::Operator(OperatorType::Coalesce) if missing_left.last() == Some(&index) => {
+                    missing_left.pop();
+                }
+                Value::Operator(op) if op.is_postfix() => {
+                    return Err(EvalError::UnsupportedNumber(op.to_string()))
+                }
+                Value::Operator(op) => {
+                    let second = value_stack.pop().ok_or(EvalError::NotEnoughValues)?;
+                    let first = value_stack.pop().ok_or(EvalError::NotEnoughValues)?;
+                    value_stack.push(eval_operator(op, first, second, self.overflow)?);
+                }
+                Value::Function(function, arg_count) => {
+                    let start = value_stack
+                        .len()
+                        .checked_sub(*arg_count)
+                        .ok_or(EvalError::NotEnoughValues)?;
+                    let args = value_stack.split_off(start);
+                    value_stack.push(eval_function(function, args)?);
+                }
+                Value::List(len) => {
+                    let start = value_stack
+                        .len()
+                        .checked_sub(*len)
+                        .ok_or(EvalError::NotEnoughValues)?;
+                    let nums = value_stack
+                        .split_off(start)
+                        .into_iter()
+                        .map(|element| match element {
+                            EmitResult::Number(num) => Ok(num),
+                            _ => Err(EvalError::InvalidListElement(element.result_type())),
+                        })
+                        .collect::<Result<Vec<N>, EvalError>>()?;
+                    value_stack.push(EmitResult::List(nums));
+                }
+            }
+        }
+
+        if value_stack.len() > 1 {
+            return Err(EvalError::TooMuchValues);
+        }
+        value_stack.pop().ok_or(EvalError::NotEnoughValues)
+    }
+}
+
+fn eval_operator<N: Num>(
+    op: &OperatorType,
+    first: EmitResult<N>,
+    second: EmitResult<N>,
+    overflow: Overflow,
+) -> Result<EmitResult<N>, EvalError> {
+    let mismatch = |first: &EmitResult<N>, second: &EmitResult<N>| EvalError::TypeMismatch {
+        operator: op.clone(),
+        left: first.result_type(),
+        right: second.result_type(),
+    };
+
+    match (first, second) {
+        (first, _) if *op == OperatorType::Coalesce => Ok(first),
+        (EmitResult::Number(a), EmitResult::List(set)) if *op == OperatorType::In => {
+            Ok(EmitResult::Boolean(set.contains(&a)))
+        }
+        (EmitResult::Number(a), EmitResult::Number(b)) => match op {
+            OperatorType::Plus
+            | OperatorType::Minus
+            | OperatorType::Times
+            | OperatorType::Divide => a
+                .arithmetic(op, b, overflow)
+                .map(EmitResult::Number)
+                .ok_or_else(|| EvalError::Overflow(op.clone())),
+            OperatorType::LT => Ok(EmitResult::Boolean(a < b)),
+            OperatorType::LE => Ok(EmitResult::Boolean(a <= b)),
+            OperatorType::GT => Ok(EmitResult::Boolean(a > b)),
+            OperatorType::GE => Ok(EmitResult::Boolean(a >= b)),
+            OperatorType::Eq => Ok(EmitResult::Boolean(a == b)),
+            OperatorType::BitAnd | OperatorType::Custom { .. } => {
+                Err(EvalError::UnsupportedNumber(op.to_string()))
+            }
+            _ => Err(mismatch(&EmitResult::Number(a), &EmitResult::Number(b))),
+        },
+        (EmitResult::Boolean(a), EmitResult::Boolean(b)) => match op {
+            OperatorType::And => Ok(EmitResult::Boolean(a && b)),
+            OperatorType::Or => Ok(EmitResult::Boolean(a || b)),
+            OperatorType::Implies => Ok(EmitResult::Boolean(!a || b)),
+            _ => Err(mismatch(&EmitResult::Boolean(a), &EmitResult::Boolean(b))),
+        },
+        (first, second) => Err(mismatch(&first, &second)),
+    }
+}
+
+fn eval_function<N: Num>(
+    function: &FunctionType,
+    mut args: Vec<EmitResult<N>>,
+) -> Result<EmitResult<N>, EvalError> {
+    match function {
+        // branches keep their type
+        FunctionType::If => {
+            let else_val = args.pop().ok_or(EvalError::NotEnoughValues)?;
+            let then_val = args.pop().ok_or(EvalError::NotEnoughValues)?;
+            match args.pop().ok_or(EvalError::NotEnoughValues)? {
+                EmitResult::Boolean(condition) => Ok(if condition { then_val } else { else_val }),
+                condition => Err(EvalError::ArgumentTypeMismatch {
+                    function: function.clone(),
+                    expected: VarType::Boolean,
+                    found: condition.result_type(),
+                }),
+            }
+        }
+        FunctionType::FirstTrue => {
+            let mut index = 0;
+            for (position, arg) in args.into_iter().enumerate() {
+                match arg {
+                    EmitResult::Boolean(true) if index == 0 => index = position + 1,
+                    EmitResult::Boolean(_) => {}
+                    _ => {
+                        return Err(EvalError::ArgumentTypeMismatch {
+                            function: function.clone(),
+                            expected: VarType::Boolean,
+                            found: arg.result_type(),
+                        })
+                    }
+                }
+            }
+            let index = index
+                .to_string()
+                .parse()
+                .map_err(|_| EvalError::UnsupportedNumber(function.to_string()))?;
+            Ok(EmitResult::Number(index))
+        }
+        FunctionType::Min | FunctionType::Max | FunctionType::Compare => {
+            let nums = args
+                .into_iter()
+                .map(|arg| match arg {
+                    EmitResult::Number(num) => Ok(num),
+                    _ => Err(EvalError::ArgumentTypeMismatch {
+                        function: function.clone(),
+                        expected: VarType::Number,
+                        found: arg.result_type(),
+                    }),
+                })
+                .collect::<Result<Vec<N>, EvalError>>()?;
+            let [a, b] = <[N; 2]>::try_from(nums).map_err(|_| EvalError::NotEnoughValues)?;
+
+            let result = match function {
+                FunctionType::Min => {
+                    if b < a {
+                        b
+                    } else {
+                        a
+                    }
+                }
+                FunctionType::Max => {
+                    if b > a {
+                        b
+                    } else {
+                        a
+                    }
+                }
+                // `N` has no conversion from integers, so the result is parsed
+                _ => {
+                    let ordering = if a < b {
+                        "-1"
+                    } else if a > b {
+                        "1"
+                    } else {
+                        "0"
+                    };
+                    ordering
+                        .parse()
+                        .map_err(|_| EvalError::UnsupportedNumber(function.to_string()))?
+                }
+            };
+            Ok(EmitResult::Number(result))
+        }
+        _ => Err(EvalError::UnsupportedNumber(function.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Num, NumEmitter, Overflow};
+    use crate::{
+        emitter::{EmitResult, EvalError},
+        parser::{OperatorType, Value},
+    };
+    use pretty_assertions::assert_eq;
+    use std::{
+        ops::{Add, Div, Mul, Sub},
+        str::FromStr,
+    };
+
+    // fixed-point number with two decimal places
+    #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+    struct Cents(i64);
+
+    impl FromStr for Cents {
+        type Err = ();
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let (whole, fraction) = s.split_once('.').unwrap_or((s, "0"));
+            let fraction = format!("{fraction:0<2}");
+            if fraction.len() > 2 {
+                return Err(());
+            }
+            let whole: i64 = whole.parse().map_err(|_| ())?;
+            let fraction: i64 = fraction.parse().map_err(|_| ())?;
+            let cents = whole.abs() * 100 + fraction;
+            Ok(Cents(if s.starts_with('-') { -cents } else { cents }))
+        }
+    }
+
+    impl Num for Cents {}
+
+    impl Add for Cents {
+        type Output = Self;
+        fn add(self, other: Self) -> Self {
+            Cents(self.0 + other.0)
+        }
+    }
+
+    impl Sub for Cents {
+        type Output = Self;
+        fn sub(self, other: Self) -> Self {
+            Cents(self.0 - other.0)
+        }
+    }
+
+    impl Mul for Cents {
+        type Output = Self;
+        fn mul(self, other: Self) -> Self {
+            Cents(self.0 * other.0 / 100)
+        }
+    }
+
+    impl Div for Cents {
+        type Output = Self;
+        fn div(self, other: Self) -> Self {
+            Cents(self.0 * 100 / other.0)
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let emitter = NumEmitter::<Cents>::parse("0.1 + 0x10").unwrap();
+        assert_eq!(
+            emitter.rpn(),
+            &vec![
+                Value::Number(Cents(10)),
+                Value::Number(Cents(1600)),
+                Value::Operator(OperatorType::Plus),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_eval_with() {
+        let eval = |expr: &str| {
+            NumEmitter::<Cents>::parse(expr)
+                .unwrap()
+                .eval_with(|_| None)
+        };
+
+        assert_eq!(eval("0.10 + 0.20"), Ok(EmitResult::Number(Cents(30))));
+        assert_eq!(eval("19.99 * 3"), Ok(EmitResult::Number(Cents(5997))));
+        assert_eq!(eval("10 / 4"), Ok(EmitResult::Number(Cents(250))));
+        assert_eq!(eval("0.1 + 0.2 = 0.3"), Ok(EmitResult::Boolean(true)));
+        assert_eq!(
+            eval("if(1 < 2 && true, max(1.5, 2.5), 0)"),
+            Ok(EmitResult::Number(Cents(250)))
+        );
+        assert_eq!(eval("compare(1, 2)"), Ok(EmitResult::Number(Cents(-100))));
+        assert_eq!(
+            eval("(1, 2.5)"),
+            Ok(EmitResult::List(vec![Cents(100), Cents(250)]))
+        );
+        assert_eq!(eval("{x} ?? 5"), Ok(EmitResult::Number(Cents(500))));
+        assert_eq!(eval("0.5 in (0.25, 0.5)"), Ok(EmitResult::Boolean(true)));
+        assert_eq!(
+            eval("sqrt(4)"),
+            Err(EvalError::UnsupportedNumber("sqrt".to_string()))
+        );
+        assert_eq!(
+            eval("{x} + 1"),
+            Err(EvalError::UnboundVariable("x".to_string()))
+        );
+
+        let emitter = NumEmitter::<Cents>::parse("{price} * {quantity}").unwrap();
+        let total = emitter.eval_with(|name| match name {
+            "price" => Some(Cents(1999)),
+            "quantity" => Some(Cents(300)),
+            _ => None,
+        });
+        assert_eq!(total, Ok(EmitResult::Number(Cents(5997))));
+    }
+
+    #[test]
+    fn test_overflow() {
+        let eval = |expr: &str, overflow: Overflow| {
+            let mut emitter = NumEmitter::<i64>::parse(expr).unwrap();
+            emitter.set_overflow(overflow);
+            emitter.eval_with(|_| None)
+        };
+        let max = "9223372036854775807";
+
+        assert_eq!(
+            eval(&format!("{max} + 1"), Overflow::Checked),
+            Err(EvalError::Overflow(OperatorType::Plus))
+        );
+        assert_eq!(
+            eval(&format!("{max} + 1"), Overflow::Wrapping),
+            Ok(EmitResult::Number(i64::MIN))
+        );
+        assert_eq!(
+            eval(&format!("{max} + 1"), Overflow::Saturating),
+            Ok(EmitResult::Number(i64::MAX))
+        );
+
+        assert_eq!(
+            eval(&format!("0 - {max} - 2"), Overflow::Checked),
+            Err(EvalError::Overflow(OperatorType::Minus))
+        );
+        assert_eq!(
+            eval(&format!("0 - {max} - 2"), Overflow::Wrapping),
+            Ok(EmitResult::Number(i64::MAX))
+        );
+        assert_eq!(
+            eval(&format!("{max} * 2"), Overflow::Saturating),
+            Ok(EmitResult::Number(i64::MAX))
+        );
+        assert_eq!(
+            eval(&format!("{max} * 2"), Overflow::Wrapping),
+            Ok(EmitResult::Number(-2))
+        );
+
+        assert_eq!(eval("7 / 2", Overflow::Checked), Ok(EmitResult::Number(3)));
+        assert_eq!(
+            eval("1 / 0", Overflow::Saturating),
+            Err(EvalError::Overflow(OperatorType::Divide))
+        );
+    }
+}
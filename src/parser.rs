@@ -1,9 +1,15 @@
-use crate::tokenizer::{TokenList, TokenType};
-use std::collections::VecDeque;
+use crate::{
+    context::{Associativity, FunctionRegistry, OperatorTable},
+    emitter::{EmitResult, EvalError},
+    numeric::Num,
+    tokenizer::{TokenList, TokenType},
+    type_checker::VarType,
+};
+use std::collections::{HashMap, VecDeque};
 
-pub type Rpn = Vec<Value>;
+pub type Rpn<N = f32> = Vec<Value<N>>;
 
-#[derive(PartialEq, Eq, Debug, Clone)]
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
 pub enum OperatorType {
     /// +
     Plus,
@@ -17,12 +23,24 @@ pub enum OperatorType {
     /// /
     Divide,
 
+    /// &, operands are truncated to integers
+    BitAnd,
+
+    /// ! (postfix), factorial of a non-negative integer
+    Factorial,
+
     /// &&
     And,
 
     /// ||
     Or,
 
+    /// =>, implication, false only if the left operand is true and the right one is false
+    Implies,
+
+    /// ??, the right operand if the left operand is an unbound variable
+    Coalesce,
+
     /// <
     LT,
 
@@ -38,61 +56,227 @@ pub enum OperatorType {
     /// =
     Eq,
 
+    /// in, true if the left operand is an element of the list on the right, e.g. `2 in (1, 2)`
+    In,
+
+    /// operator on numbers registered in an [`OperatorTable`], evaluated by the emitter
+    Custom {
+        symbol: String,
+        priority: u32,
+        associativity: Associativity,
+    },
+
     /// (
     LeftParenthesis,
 }
 
 #[derive(Debug, PartialEq)]
 pub enum ParserError {
-    NoMatchingLeftParenthesis,
-    ExtraLeftParenthesis,
+    // number of unmatched left and right parentheses
+    UnbalancedParentheses {
+        open: usize,
+        close: usize,
+    },
+    // contains the number token value
+    InvalidNumber(String),
+    // contains the placeholder token value, which should be its index
+    InvalidPlaceholder(String),
+    // contains the function name
+    UnknownFunction(String),
+    // contains the identifier name
+    UnknownIdentifier(String),
+    // contains the operator symbol, which has no builtin or registered definition
+    UnknownOperator(String),
+    InvalidArgumentCount {
+        function: FunctionType,
+        found: usize,
+    },
+    SeparatorOutsideFunction,
+    // operator following another operator, or postfix operator without an operand
+    UnexpectedOperator(OperatorType),
+    // binary operator at the start or the end of an expression or parentheses
+    MissingOperand(OperatorType),
+    // `()` which is not the argument list of a function
+    EmptyParentheses,
+    // `,` directly following `(` or `,`, or `)` directly following `,`
+    MissingElement,
+    // contains the token value of an operand directly following another operand
+    MissingOperator(String),
 }
 
 impl std::fmt::Display for ParserError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ParserError::NoMatchingLeftParenthesis => {
-                write!(f, "right parenthesis has no matching left parenthesis")
+            ParserError::UnbalancedParentheses { open, close } => {
+                write!(
+                    f,
+                    "unbalanced parentheses: {open} unmatched `(` and {close} unmatched `)`"
+                )
+            }
+            Self::InvalidNumber(value) => {
+                write!(f, "invalid number: '{value}'")
+            }
+            Self::InvalidPlaceholder(value) => {
+                write!(f, "invalid placeholder index: '{value}'")
+            }
+            Self::UnknownFunction(name) => {
+                write!(f, "unknown function: '{name}'")
+            }
+            Self::UnknownIdentifier(name) => {
+                write!(f, "unknown identifier: '{name}'")
+            }
+            Self::UnknownOperator(symbol) => {
+                write!(f, "unknown operator: '{symbol}'")
+            }
+            Self::InvalidArgumentCount { function, found } => {
+                let at_least = if function.is_variadic() {
+                    "at least "
+                } else {
+                    ""
+                };
+                write!(
+                    f,
+                    "function `{function}` expects {at_least}{} argument(s), found {found}",
+                    function.arity()
+                )
+            }
+            Self::SeparatorOutsideFunction => {
+                write!(
+                    f,
+                    "`,` can only separate function arguments or list elements"
+                )
+            }
+            Self::UnexpectedOperator(op) => {
+                write!(f, "operator `{op}` is missing its left operand")
+            }
+            Self::MissingOperand(op) => {
+                write!(f, "operator `{op}` is missing an operand")
             }
-            Self::ExtraLeftParenthesis => {
-                write!(f, "extra left parenthesis")
+            Self::EmptyParentheses => {
+                write!(f, "empty parentheses are only allowed for function calls")
+            }
+            Self::MissingElement => {
+                write!(f, "missing list element or function argument around `,`")
+            }
+            Self::MissingOperator(value) => {
+                write!(f, "missing operator before '{value}'")
             }
         }
     }
 }
 
+impl std::fmt::Display for OperatorType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self {
+            Self::Plus => "+",
+            Self::Minus => "-",
+            Self::Times => "*",
+            Self::Divide => "/",
+            Self::BitAnd => "&",
+            Self::Factorial => "!",
+            Self::And => "&&",
+            Self::Or => "||",
+            Self::Implies => "=>",
+            Self::Coalesce => "??",
+            Self::LT => "<",
+            Self::LE => "<=",
+            Self::GT => ">",
+            Self::GE => ">=",
+            Self::Eq => "=",
+            Self::In => "in",
+            Self::Custom { symbol, .. } => symbol,
+            Self::LeftParenthesis => "(",
+        };
+        write!(f, "{symbol}")
+    }
+}
+
 impl OperatorType {
-    fn from_str(str: &str) -> Self {
-        match str {
+    /// operators resulting in a number
+    pub const ARITHMETIC: [OperatorType; 6] = [
+        Self::Plus,
+        Self::Minus,
+        Self::Times,
+        Self::Divide,
+        Self::BitAnd,
+        Self::Factorial,
+    ];
+
+    // `None` for symbols which are not builtin operators
+    pub(crate) fn from_str(str: &str) -> Option<Self> {
+        let op = match str {
             "+" => OperatorType::Plus,
             "-" => OperatorType::Minus,
             "*" => Self::Times,
             "/" => Self::Divide,
+            "&" => Self::BitAnd,
+            "!" => Self::Factorial,
             "&&" => Self::And,
             "||" => Self::Or,
+            "=>" => Self::Implies,
+            "??" => Self::Coalesce,
             "<" => Self::LT,
             "<=" => Self::LE,
             ">" => Self::GT,
             ">=" => Self::GE,
-            "=" => Self::Eq,
-            _ => unreachable!("invalid value: {str}"),
-        }
+            "=" | "==" => Self::Eq,
+            "in" => Self::In,
+            _ => return None,
+        };
+        Some(op)
     }
 
     /// https://en.wikipedia.org/wiki/Order_of_operations#Programming_languages
-    /// the higher the number, the higher the priority
-    fn get_priority(&self) -> u32 {
-        match self {
-            Self::LeftParenthesis => {
-                unreachable!("trying to get priority of paranthesis")
+    /// the higher the number, the higher the priority, `None` for the parenthesis
+    pub fn priority(&self) -> Option<u32> {
+        let priority = match self {
+            Self::LeftParenthesis => return None,
+
+            Self::Coalesce => 0,
+            Self::Implies => 1,
+            Self::Or => 2,
+            Self::And => 3,
+            Self::BitAnd => 4,
+            Self::Eq => 5,
+            Self::LT | Self::LE | Self::GT | Self::GE | Self::In => 6,
+            Self::Plus | Self::Minus => 7,
+            Self::Times | Self::Divide => 8,
+            Self::Factorial => 9,
+            Self::Custom { priority, .. } => *priority,
+        };
+        Some(priority)
+    }
+
+    /// only custom operators can be right associative
+    pub fn is_right_associative(&self) -> bool {
+        matches!(
+            self,
+            Self::Custom {
+                associativity: Associativity::Right,
+                ..
             }
+        )
+    }
+
+    /// postfix operators have a single operand preceding them
+    pub fn is_postfix(&self) -> bool {
+        *self == Self::Factorial
+    }
 
-            Self::Or => 1,
-            Self::And => 2,
-            Self::Eq => 3,
-            Self::LT | Self::LE | Self::GT | Self::GE => 4,
-            Self::Plus | Self::Minus => 5,
-            Self::Times | Self::Divide => 6,
+    /// returns `None` if the operand is not valid for the operator
+    pub fn eval_postfix(&self, operand: f32) -> Option<f32> {
+        match self {
+            Self::Factorial => {
+                if operand < 0.0 || operand.fract() != 0.0 {
+                    return None;
+                }
+                // 35! overflows f32
+                if operand > 34.0 {
+                    return Some(f32::INFINITY);
+                }
+                Some((1..=operand as u32).map(|num| num as f32).product())
+            }
+            _ => panic!("operator `{self}` is not postfix"),
         }
     }
 
@@ -106,12 +290,17 @@ impl OperatorType {
             Self::Minus => first - second,
             Self::Times => first * second,
             Self::Divide => first / second,
-            Self::And | Self::Or => {
+            Self::BitAnd => (first as i64 & second as i64) as f32,
+            Self::Factorial => panic!("method `eval_postfix` should be used instead"),
+            Self::And | Self::Or | Self::Implies => {
                 panic!("method `eval_conditional` should be used instead")
             }
             Self::LT | Self::LE | Self::GT | Self::GE | Self::Eq => {
                 panic!("method `eval_comparison` should be used instead")
             }
+            Self::Coalesce => panic!("operator `??` is evaluated by the emitter"),
+            Self::In => panic!("method `try_eval` should be used instead"),
+            Self::Custom { .. } => panic!("custom operator `{self}` is evaluated by the emitter"),
         }
     }
 
@@ -121,29 +310,39 @@ impl OperatorType {
                 unreachable!("OperatorType parenthesis")
             }
 
-            Self::Plus | Self::Minus | Self::Times | Self::Divide => {
+            Self::Plus | Self::Minus | Self::Times | Self::Divide | Self::BitAnd => {
                 panic!("method `eval_nums` should be used instead")
             }
+            Self::Factorial => panic!("method `eval_postfix` should be used instead"),
             Self::And => first && second,
             Self::Or => first || second,
+            Self::Implies => !first || second,
             Self::LT | Self::LE | Self::GT | Self::GE | Self::Eq => {
                 panic!("method `eval_comparison` should be used instead")
             }
+            Self::Coalesce => panic!("operator `??` is evaluated by the emitter"),
+            Self::In => panic!("method `try_eval` should be used instead"),
+            Self::Custom { .. } => panic!("custom operator `{self}` is evaluated by the emitter"),
         }
     }
 
+    /// Follows IEEE 754, every comparison with a `NaN` operand is false, including `NaN = NaN`.
     pub fn eval_comparison(&self, first: f32, second: f32) -> bool {
         match self {
             Self::LeftParenthesis => {
                 unreachable!("operatorType parenthesis")
             }
 
-            Self::Plus | Self::Minus | Self::Times | Self::Divide => {
+            Self::Plus | Self::Minus | Self::Times | Self::Divide | Self::BitAnd => {
                 panic!("method `eval_nums` should be used instead.")
             }
-            Self::And | Self::Or => {
+            Self::And | Self::Or | Self::Implies => {
                 panic!("method `eval_conditional` should be used instead.")
             }
+            Self::Factorial => panic!("method `eval_postfix` should be used instead."),
+            Self::Coalesce => panic!("operator `??` is evaluated by the emitter."),
+            Self::In => panic!("method `try_eval` should be used instead."),
+            Self::Custom { .. } => panic!("custom operator `{self}` is evaluated by the emitter."),
             Self::LT => first < second,
             Self::LE => first <= second,
             Self::GT => first > second,
@@ -151,103 +350,723 @@ impl OperatorType {
             Self::Eq => first == second,
         }
     }
+
+    /// Evaluates a binary operator on operands of any type, unlike `eval_nums`, `eval_comparison`
+    /// and `eval_conditional` which panic when called for an operator of another category.
+    /// `??` returns the left operand, as it is only evaluated when the left operand is bound.
+    /// Custom operators fail with [`EvalError::UnknownOperator`], they need the operator table.
+    pub fn try_eval(&self, first: EmitResult, second: EmitResult) -> Result<EmitResult, EvalError> {
+        let mismatch = || EvalError::TypeMismatch {
+            operator: self.clone(),
+            left: first.result_type(),
+            right: second.result_type(),
+        };
+        match self {
+            Self::LeftParenthesis | Self::Factorial => {
+                Err(EvalError::NotBinaryOperator(self.clone()))
+            }
+            Self::Coalesce => Ok(first),
+            Self::Custom { symbol, .. } => Err(EvalError::UnknownOperator(symbol.clone())),
+            Self::Plus | Self::Minus | Self::Times | Self::Divide | Self::BitAnd => {
+                match (&first, &second) {
+                    (EmitResult::Number(a), EmitResult::Number(b)) => {
+                        Ok(EmitResult::Number(self.eval_nums(*a, *b)))
+                    }
+                    _ => Err(mismatch()),
+                }
+            }
+            Self::LT | Self::LE | Self::GT | Self::GE | Self::Eq => match (&first, &second) {
+                (EmitResult::Number(a), EmitResult::Number(b)) => {
+                    Ok(EmitResult::Boolean(self.eval_comparison(*a, *b)))
+                }
+                _ => Err(mismatch()),
+            },
+            Self::In => match (&first, &second) {
+                (EmitResult::Number(a), EmitResult::List(set)) => {
+                    Ok(EmitResult::Boolean(set.contains(a)))
+                }
+                _ => Err(mismatch()),
+            },
+            Self::And | Self::Or | Self::Implies => match (&first, &second) {
+                (EmitResult::Boolean(a), EmitResult::Boolean(b)) => {
+                    Ok(EmitResult::Boolean(self.eval_conditional(*a, *b)))
+                }
+                _ => Err(mismatch()),
+            },
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum FunctionType {
+    /// abs(x)
+    Abs,
+
+    /// sign(x), returns -1, 0 or 1
+    Sign,
+
+    /// sqrt(x)
+    Sqrt,
+
+    /// log(x), natural logarithm, `NaN` for negative and `-inf` for zero arguments
+    Log,
+
+    /// log(base, x)
+    LogBase,
+
+    /// log10(x)
+    Log10,
+
+    /// log2(x)
+    Log2,
+
+    /// round(x), halfway values are rounded away from zero
+    Round,
+
+    /// round_half_even(x), halfway values are rounded to the nearest even integer
+    RoundHalfEven,
+
+    /// min(x, y)
+    Min,
+
+    /// max(x, y)
+    Max,
+
+    /// pow(base, exponent)
+    Pow,
+
+    /// sin(x), `x` is in radians unless the angle mode says otherwise
+    Sin,
+
+    /// cos(x)
+    Cos,
+
+    /// tan(x)
+    Tan,
+
+    /// compare(x, y), returns -1 if `x < y`, 1 if `x > y` and 0 otherwise, including `NaN` operands
+    Compare,
+
+    /// if(condition, then, else), both branches are evaluated
+    If,
+
+    /// between(x, low, high), true if `low <= x <= high`
+    Between,
+
+    /// first_true(c1, c2, ...), 1-based index of the first true condition, 0 if none is true
+    FirstTrue,
+
+    /// function from a [`FunctionRegistry`]
+    Custom { name: String, arity: usize },
+}
+
+impl std::fmt::Display for FunctionType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Abs => "abs",
+            Self::Sign => "sign",
+            Self::Sqrt => "sqrt",
+            Self::Log | Self::LogBase => "log",
+            Self::Log10 => "log10",
+            Self::Log2 => "log2",
+            Self::Round => "round",
+            Self::RoundHalfEven => "round_half_even",
+            Self::Min => "min",
+            Self::Max => "max",
+            Self::Pow => "pow",
+            Self::Sin => "sin",
+            Self::Cos => "cos",
+            Self::Tan => "tan",
+            Self::Compare => "compare",
+            Self::If => "if",
+            Self::Between => "between",
+            Self::FirstTrue => "first_true",
+            Self::Custom { name, .. } => name,
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FunctionType {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "abs" => Some(Self::Abs),
+            "sign" => Some(Self::Sign),
+            "sqrt" => Some(Self::Sqrt),
+            "log" => Some(Self::Log),
+            "log10" => Some(Self::Log10),
+            "log2" => Some(Self::Log2),
+            "round" => Some(Self::Round),
+            "round_half_even" => Some(Self::RoundHalfEven),
+            "min" => Some(Self::Min),
+            "max" => Some(Self::Max),
+            "pow" => Some(Self::Pow),
+            "sin" => Some(Self::Sin),
+            "cos" => Some(Self::Cos),
+            "tan" => Some(Self::Tan),
+            "compare" => Some(Self::Compare),
+            "if" => Some(Self::If),
+            "between" => Some(Self::Between),
+            "first_true" => Some(Self::FirstTrue),
+            _ => None,
+        }
+    }
+
+    /// number of arguments the function accepts, the minimum for variadic functions
+    pub fn arity(&self) -> usize {
+        match self {
+            Self::FirstTrue
+            | Self::Abs
+            | Self::Sign
+            | Self::Sqrt
+            | Self::Log
+            | Self::Log10
+            | Self::Log2
+            | Self::Round
+            | Self::RoundHalfEven
+            | Self::Sin
+            | Self::Cos
+            | Self::Tan => 1,
+            Self::Min | Self::Max | Self::Pow | Self::Compare | Self::LogBase => 2,
+            Self::If | Self::Between => 3,
+            Self::Custom { arity, .. } => *arity,
+        }
+    }
+
+    // `log` takes either one or two arguments
+    fn with_arg_count(self, arg_count: usize) -> Self {
+        match (self, arg_count) {
+            (Self::Log, 2) => Self::LogBase,
+            (function, _) => function,
+        }
+    }
+
+    /// variadic functions accept any number of arguments from their arity up
+    pub fn is_variadic(&self) -> bool {
+        *self == Self::FirstTrue
+    }
+
+    /// whether the function can be called with `arg_count` arguments
+    pub fn accepts_arg_count(&self, arg_count: usize) -> bool {
+        if self.is_variadic() {
+            arg_count >= self.arity()
+        } else {
+            arg_count == self.arity()
+        }
+    }
+
+    pub fn is_logarithm(&self) -> bool {
+        matches!(self, Self::Log | Self::LogBase | Self::Log10 | Self::Log2)
+    }
+
+    pub fn is_trigonometric(&self) -> bool {
+        matches!(self, Self::Sin | Self::Cos | Self::Tan)
+    }
+
+    /// Type of the function result, booleans are returned as `1` or `0` by `eval_nums`.
+    /// `if` returns the type of its branches, which is a number in `eval_nums`.
+    pub fn result_type(&self) -> VarType {
+        match self {
+            Self::Between => VarType::Boolean,
+            _ => VarType::Number,
+        }
+    }
+
+    pub fn eval_nums(&self, args: &[f32]) -> f32 {
+        match self {
+            Self::Abs => args[0].abs(),
+            // `f32::signum` returns 1 for zero
+            Self::Sign => {
+                if args[0] == 0.0 {
+                    0.0
+                } else {
+                    args[0].signum()
+                }
+            }
+            Self::Sqrt => args[0].sqrt(),
+            Self::Log => args[0].ln(),
+            Self::LogBase => args[1].log(args[0]),
+            Self::Log10 => args[0].log10(),
+            Self::Log2 => args[0].log2(),
+            Self::Round => args[0].round(),
+            Self::RoundHalfEven => args[0].round_ties_even(),
+            Self::Min => args[0].min(args[1]),
+            Self::Max => args[0].max(args[1]),
+            Self::Pow => args[0].powf(args[1]),
+            Self::Sin => args[0].sin(),
+            Self::Cos => args[0].cos(),
+            Self::Tan => args[0].tan(),
+            Self::Compare => match args[0].partial_cmp(&args[1]) {
+                Some(std::cmp::Ordering::Less) => -1.0,
+                Some(std::cmp::Ordering::Greater) => 1.0,
+                Some(std::cmp::Ordering::Equal) | None => 0.0,
+            },
+            // nonzero condition is true
+            Self::If => {
+                if args[0] != 0.0 {
+                    args[1]
+                } else {
+                    args[2]
+                }
+            }
+            Self::Between => {
+                if args[1] <= args[0] && args[0] <= args[2] {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Self::FirstTrue => args
+                .iter()
+                .position(|arg| *arg != 0.0)
+                .map_or(0.0, |index| (index + 1) as f32),
+            Self::Custom { name, .. } => {
+                panic!("function `{name}` has to be called through a `FunctionRegistry`")
+            }
+        }
+    }
 }
 
+/// `N` is the number type, see [`Parser::parse_as`]
 #[derive(Debug, PartialEq, Clone)]
-pub enum Value {
+pub enum Value<N = f32> {
     Operator(OperatorType),
-    Number(f32),
+    Number(N),
     Boolean(bool),
     Variable(String),
+    /// `?` filled positionally, the index among the placeholders of the expression
+    Placeholder(usize),
+    /// function with the number of arguments it was called with
+    Function(FunctionType, usize),
+    /// list of the given number of preceding values, e.g. `(1, 2, 3)`
+    List(usize),
+}
+
+/// parses decimal, hexadecimal (`0x`) and binary (`0b`) number literals
+fn parse_number<N: Num>(literal: &str) -> Option<N> {
+    let radix = |digits: &str, radix: u32| {
+        let num = i64::from_str_radix(digits, radix).ok()?;
+        num.to_string().parse().ok()
+    };
+    if let Some(hex) = literal.strip_prefix("0x") {
+        return radix(hex, 16);
+    }
+    if let Some(binary) = literal.strip_prefix("0b") {
+        return radix(binary, 2);
+    }
+
+    // digit separators and decimal comma, see `Tokenizer::with_decimal_comma`
+    literal.replace('_', "").replace(',', ".").parse().ok()
+}
+
+/// converts `f32` numbers through their string representation
+fn convert_number<N: Num>(value: &Value) -> Result<Value<N>, ParserError> {
+    Ok(match value {
+        Value::Number(num) => {
+            let num = num.to_string();
+            Value::Number(num.parse().map_err(|_| ParserError::InvalidNumber(num))?)
+        }
+        Value::Operator(op) => Value::Operator(op.clone()),
+        Value::Boolean(boolean) => Value::Boolean(*boolean),
+        Value::Variable(name) => Value::Variable(name.to_owned()),
+        Value::Placeholder(index) => Value::Placeholder(*index),
+        Value::Function(function, arg_count) => Value::Function(function.clone(), *arg_count),
+        Value::List(len) => Value::List(*len),
+    })
+}
+
+/// returns the number of unmatched left and right parentheses
+fn unbalanced_parentheses(tokens: &TokenList) -> (usize, usize) {
+    let mut depth = 0;
+    let mut close = 0;
+    for token in tokens
+        .iter()
+        .filter(|token| token.token_type == TokenType::Parenthesis)
+    {
+        if token.value == "(" {
+            depth += 1;
+        } else if depth == 0 {
+            close += 1;
+        } else {
+            depth -= 1;
+        }
+    }
+
+    (depth, close)
+}
+
+/// kind of the previously parsed token, used to validate what can follow it
+#[derive(PartialEq, Clone, Copy)]
+enum Previous {
+    Start,
+    Operand,
+    Operator,
+    Function,
+    LeftParenthesis,
+    Separator,
+}
+
+/// Constants of [`Parser::with_scientific_constants`], as name and value.
+pub const SCIENTIFIC_CONSTANTS: [(&str, f32); 6] = [
+    ("pi", std::f32::consts::PI),
+    ("e", std::f32::consts::E),
+    ("tau", std::f32::consts::TAU),
+    // golden ratio
+    ("phi", 1.618_034),
+    ("sqrt2", std::f32::consts::SQRT_2),
+    ("inf", f32::INFINITY),
+];
+
+/// Converts tokens to reverse polish notation, configured with the `with_*` methods.
+#[derive(Debug, Clone, Default)]
+pub struct Parser {
+    constants: HashMap<String, f32>,
+    macros: HashMap<String, Rpn>,
+    functions: FunctionRegistry,
+    operators: OperatorTable,
+    implicit_multiplication: bool,
+    scientific_constants: bool,
 }
 
 pub fn tokens_to_rpn(tokens: TokenList) -> Result<Rpn, ParserError> {
-    // queue - last index in, 0th index out
-    let mut token_queue: Rpn = vec![];
-    // stack - 0th index in, 0th index out
-    let mut operator_stack: VecDeque<OperatorType> = VecDeque::new();
-
-    for token in tokens {
-        match token.token_type {
-            TokenType::Number => token_queue.push(Value::Number(
-                token.value.parse().expect("failed to parse float"),
-            )),
-            TokenType::Boolean => token_queue.push(Value::Boolean(match token.value.as_str() {
-                "true" => true,
-                "false" => false,
-                _ => unreachable!(
-                    "token of type parenthesis has invalid value, value: {}",
-                    token.value
-                ),
-            })),
-            TokenType::Variable => token_queue.push(Value::Variable(token.value.clone())),
-            TokenType::Parenthesis => match token.value.as_str() {
-                "(" => operator_stack.push_front(OperatorType::LeftParenthesis),
-                ")" => {
-                    while !operator_stack.is_empty()
-                        && operator_stack[0] != OperatorType::LeftParenthesis
+    Parser::new().parse(tokens)
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Named constants, e.g. `pi`, that are replaced by their value during parsing.
+    pub fn with_constants(mut self, constants: HashMap<String, f32>) -> Self {
+        self.constants = constants;
+        self
+    }
+
+    /// Named sub-expressions, e.g. `dist`, whose reverse polish notation replaces
+    /// the name during parsing. Unlike constants, macros can reference variables.
+    pub fn with_macros(mut self, macros: HashMap<String, Rpn>) -> Self {
+        self.macros = macros;
+        self
+    }
+
+    /// Custom functions callable in addition to the built-in ones.
+    pub fn with_functions(mut self, functions: FunctionRegistry) -> Self {
+        self.functions = functions;
+        self
+    }
+
+    /// Custom binary operators in addition to the built-in ones, the tokenizer has to match
+    /// their symbols, see [`crate::Tokenizer::with_operator_symbols`].
+    pub fn with_operators(mut self, operators: OperatorTable) -> Self {
+        self.operators = operators;
+        self
+    }
+
+    /// Multiplies an operand directly followed by a variable or `(`, e.g. `2{x}` or `3(1 + 1)`,
+    /// instead of failing with [`ParserError::MissingOperator`].
+    pub fn with_implicit_multiplication(mut self, enabled: bool) -> Self {
+        self.implicit_multiplication = enabled;
+        self
+    }
+
+    /// Resolves the names of [`SCIENTIFIC_CONSTANTS`] not defined by `with_constants`.
+    pub fn with_scientific_constants(mut self, enabled: bool) -> Self {
+        self.scientific_constants = enabled;
+        self
+    }
+
+    // value of a constant defined with `with_constants` or a scientific one
+    fn constant(&self, name: &str) -> Option<f32> {
+        self.constants.get(name).copied().or_else(|| {
+            SCIENTIFIC_CONSTANTS
+                .iter()
+                .find(|(scientific, _)| self.scientific_constants && *scientific == name)
+                .map(|(_, value)| *value)
+        })
+    }
+
+    pub fn parse(&self, tokens: TokenList) -> Result<Rpn, ParserError> {
+        self.parse_as(tokens)
+    }
+
+    /// Parses number literals as `N` instead of `f32`, e.g. for exact decimal arithmetic.
+    /// Constants are converted through their `f32` string representation.
+    pub fn parse_as<N: Num>(&self, tokens: TokenList) -> Result<Rpn<N>, ParserError> {
+        let (open, close) = unbalanced_parentheses(&tokens);
+        if open > 0 || close > 0 {
+            return Err(ParserError::UnbalancedParentheses { open, close });
+        }
+
+        // queue - last index in, 0th index out
+        let mut token_queue: Rpn<N> = vec![];
+        // stack - 0th index in, 0th index out
+        let mut operator_stack: VecDeque<OperatorType> = VecDeque::new();
+        // one entry for every open parenthesis - the called function (if any) and the argument count
+        let mut call_stack: Vec<(Option<FunctionType>, usize)> = vec![];
+        // function whose argument list starts with the next left parenthesis
+        let mut pending_function: Option<FunctionType> = None;
+        let mut previous = Previous::Start;
+
+        // whitespace is skipped, so that a function name can be followed by a space
+        let mut tokens = tokens
+            .into_iter()
+            .filter(|token| token.token_type != TokenType::Whitespace)
+            .peekable();
+        while let Some(token) = tokens.next() {
+            let token_type = token.token_type;
+            let is_left_parenthesis =
+                token.token_type == TokenType::Parenthesis && token.value == "(";
+            let starts_operand = is_left_parenthesis
+                || matches!(
+                    token_type,
+                    TokenType::Number
+                        | TokenType::Boolean
+                        | TokenType::Variable
+                        | TokenType::Placeholder
+                        | TokenType::Identifier
+                        | TokenType::Function
+                );
+            let ends_operand = token_type == TokenType::Comma
+                || (token_type == TokenType::Parenthesis && !is_left_parenthesis);
+
+            if starts_operand && previous == Previous::Operand {
+                let implicit = self.implicit_multiplication
+                    && (token_type == TokenType::Variable || is_left_parenthesis);
+                if !implicit {
+                    return Err(ParserError::MissingOperator(token.value));
+                }
+                Self::push_operator(OperatorType::Times, &mut operator_stack, &mut token_queue);
+            }
+            if ends_operand && previous == Previous::Operator {
+                return Err(Self::missing_right_operand(&operator_stack));
+            }
+            // `)` after `(` is an empty argument list or checked as empty parentheses
+            let empty_element = previous == Previous::Separator
+                || (token_type == TokenType::Comma && previous == Previous::LeftParenthesis);
+            if ends_operand && empty_element {
+                return Err(ParserError::MissingElement);
+            }
+
+            match token.token_type {
+                TokenType::Number => token_queue
+                    .push(Value::Number(parse_number(&token.value).ok_or_else(
+                        || ParserError::InvalidNumber(token.value.clone()),
+                    )?)),
+                TokenType::Boolean => {
+                    token_queue.push(Value::Boolean(match token.value.as_str() {
+                        "true" => true,
+                        "false" => false,
+                        _ => unreachable!(
+                            "token of type parenthesis has invalid value, value: {}",
+                            token.value
+                        ),
+                    }))
+                }
+                TokenType::Variable => token_queue.push(Value::Variable(token.value.clone())),
+                TokenType::Placeholder => token_queue.push(Value::Placeholder(
+                    token
+                        .value
+                        .parse()
+                        .map_err(|_| ParserError::InvalidPlaceholder(token.value.clone()))?,
+                )),
+                TokenType::Identifier | TokenType::Function => {
+                    let is_call = tokens.peek().is_some_and(|next| {
+                        next.token_type == TokenType::Parenthesis && next.value == "("
+                    });
+                    if is_call {
+                        let custom = |name: String| {
+                            let arity = self.functions.arity(&name)?;
+                            Some(FunctionType::Custom { name, arity })
+                        };
+                        pending_function = Some(
+                            FunctionType::from_name(&token.value)
+                                .or_else(|| custom(token.value.clone()))
+                                .ok_or(ParserError::UnknownFunction(token.value))?,
+                        );
+                    } else if let Some(rpn) = self.macros.get(&token.value) {
+                        // the macro forms a single operand, like a parenthesized expression
+                        for value in rpn {
+                            token_queue.push(convert_number(value)?);
+                        }
+                    } else {
+                        let constant = self
+                            .constant(&token.value)
+                            .ok_or(ParserError::UnknownIdentifier(token.value))?;
+                        token_queue.push(convert_number(&Value::Number(constant))?);
+                    }
+                }
+                TokenType::Comma => {
+                    while operator_stack
+                        .front()
+                        .is_some_and(|op| *op != OperatorType::LeftParenthesis)
                     {
                         token_queue.push(Value::Operator(
                             operator_stack.pop_front().unwrap_or_else(|| {
                                 panic!("could not pop first value of stack: {:#?}", operator_stack)
                             }),
-                        ))
+                        ));
+                    }
+
+                    // separates list elements in parentheses without a function
+                    match call_stack.last_mut() {
+                        Some((_, arg_count)) => *arg_count += 1,
+                        None => return Err(ParserError::SeparatorOutsideFunction),
                     }
-                    if operator_stack[0] != OperatorType::LeftParenthesis {
-                        return Err(ParserError::NoMatchingLeftParenthesis);
+                }
+                TokenType::Parenthesis => match token.value.as_str() {
+                    "(" => {
+                        operator_stack.push_front(OperatorType::LeftParenthesis);
+                        call_stack.push((pending_function.take(), 1));
                     }
+                    ")" => {
+                        if previous == Previous::LeftParenthesis
+                            && matches!(call_stack.last(), Some((None, _)))
+                        {
+                            return Err(ParserError::EmptyParentheses);
+                        }
+                        while operator_stack
+                            .front()
+                            .is_some_and(|op| *op != OperatorType::LeftParenthesis)
+                        {
+                            token_queue.push(Value::Operator(
+                                operator_stack.pop_front().unwrap_or_else(|| {
+                                    panic!(
+                                        "could not pop first value of stack: {:#?}",
+                                        operator_stack
+                                    )
+                                }),
+                            ))
+                        }
+                        // parentheses are checked to be balanced before parsing
+                        operator_stack.pop_front().unwrap_or_else(|| {
+                            panic!("could not pop first value of stack: {:#?}", operator_stack)
+                        });
 
-                    operator_stack.pop_front().unwrap_or_else(|| {
-                        panic!("could not pop first value of stack: {:#?}", operator_stack)
-                    });
+                        if let Some((None, len @ 2..)) = call_stack.last() {
+                            token_queue.push(Value::List(*len));
+                        }
+                        if let Some((Some(function), arg_count)) = call_stack.pop() {
+                            // `f()` has no arguments
+                            let arg_count = if previous == Previous::LeftParenthesis {
+                                0
+                            } else {
+                                arg_count
+                            };
+                            let function = function.with_arg_count(arg_count);
+                            if !function.accepts_arg_count(arg_count) {
+                                return Err(ParserError::InvalidArgumentCount {
+                                    function,
+                                    found: arg_count,
+                                });
+                            }
+                            token_queue.push(Value::Function(function, arg_count));
+                        }
+                    }
+                    _ => unreachable!(
+                        "token of type parenthesis has invalid value, value: {}",
+                        token.value
+                    ),
+                },
+                TokenType::Operator => {
+                    let op = self
+                        .operators
+                        .operator(&token.value)
+                        .or_else(|| OperatorType::from_str(token.value.as_str()))
+                        .ok_or_else(|| ParserError::UnknownOperator(token.value.clone()))?;
+                    if op.is_postfix() {
+                        if previous != Previous::Operand {
+                            return Err(ParserError::UnexpectedOperator(op));
+                        }
+                        // binds tighter than anything on the operator stack
+                        token_queue.push(Value::Operator(op));
+                        previous = Previous::Operand;
+                        continue;
+                    }
+                    match previous {
+                        Previous::Operator => return Err(ParserError::UnexpectedOperator(op)),
+                        Previous::Start | Previous::LeftParenthesis | Previous::Separator => {
+                            return Err(ParserError::MissingOperand(op))
+                        }
+                        Previous::Operand | Previous::Function => {}
+                    }
+
+                    Self::push_operator(op, &mut operator_stack, &mut token_queue);
                 }
-                _ => unreachable!(
-                    "token of type parenthesis has invalid value, value: {}",
-                    token.value
-                ),
-            },
-            TokenType::Operator => {
-                let op = OperatorType::from_str(token.value.as_str());
-
-                while !operator_stack.is_empty()
-                    && operator_stack[0] != OperatorType::LeftParenthesis
-                    && operator_stack[0].get_priority() >= op.get_priority()
-                {
-                    token_queue.push(Value::Operator(operator_stack.pop_front().unwrap_or_else(
-                        || panic!("could not pop first value of stack: {:#?}", operator_stack),
-                    )));
+                TokenType::Whitespace => unreachable!("whitespace tokens are filtered out"),
+            }
+
+            previous = match token_type {
+                TokenType::Operator => Previous::Operator,
+                TokenType::Comma => Previous::Separator,
+                TokenType::Parenthesis if is_left_parenthesis => Previous::LeftParenthesis,
+                TokenType::Identifier | TokenType::Function if pending_function.is_some() => {
+                    Previous::Function
                 }
+                _ => Previous::Operand,
+            };
+        }
 
-                operator_stack.push_front(op);
-            }
-            TokenType::Whitespace => unimplemented!("whitespace in token list"),
+        if previous == Previous::Operator {
+            return Err(Self::missing_right_operand(&operator_stack));
         }
-    }
 
-    while !operator_stack.is_empty() {
-        let op = operator_stack.pop_front().unwrap_or_else(|| {
-            panic!(
-                "could not pop first stack from stack: {:#?}",
-                operator_stack
-            )
-        });
+        while !operator_stack.is_empty() {
+            let op = operator_stack.pop_front().unwrap_or_else(|| {
+                panic!(
+                    "could not pop first stack from stack: {:#?}",
+                    operator_stack
+                )
+            });
+
+            token_queue.push(Value::Operator(op));
+        }
+
+        Ok(token_queue)
+    }
 
-        if op == OperatorType::LeftParenthesis {
-            return Err(ParserError::ExtraLeftParenthesis);
+    // moves operators with at least the same priority to the output before pushing `op`,
+    // only those with a higher priority if `op` is right associative
+    fn push_operator<N>(
+        op: OperatorType,
+        operator_stack: &mut VecDeque<OperatorType>,
+        token_queue: &mut Rpn<N>,
+    ) {
+        while !operator_stack.is_empty()
+            && operator_stack[0] != OperatorType::LeftParenthesis
+            && (operator_stack[0].priority() > op.priority()
+                || (operator_stack[0].priority() == op.priority() && !op.is_right_associative()))
+        {
+            token_queue.push(Value::Operator(operator_stack.pop_front().unwrap_or_else(
+                || panic!("could not pop first value of stack: {:#?}", operator_stack),
+            )));
         }
-        token_queue.push(Value::Operator(op));
+
+        operator_stack.push_front(op);
     }
 
-    Ok(token_queue)
+    // the last pushed operator is the one without its right operand
+    fn missing_right_operand(operator_stack: &VecDeque<OperatorType>) -> ParserError {
+        ParserError::MissingOperand(
+            operator_stack
+                .front()
+                .cloned()
+                .expect("operator should be on the stack"),
+        )
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
-        parser::{tokens_to_rpn, OperatorType, Value},
-        tokenizer::{Token, TokenList, TokenType},
+        context::FunctionRegistry,
+        emitter::{EmitResult, EvalError},
+        parser::{tokens_to_rpn, FunctionType, OperatorType, Parser, ParserError, Value},
+        tokenizer::{Token, TokenList, TokenType, Tokenizer},
+        type_checker::VarType,
     };
     use pretty_assertions::assert_eq;
+    use std::collections::{HashMap, HashSet};
 
     #[test]
     fn test_1() {
@@ -576,30 +1395,680 @@ mod tests {
     }
 
     #[test]
-    fn test_eval_nums() {
-        assert_eq!(OperatorType::Plus.eval_nums(2.0, 3.0), 5.0);
-        assert_eq!(OperatorType::Minus.eval_nums(5.0, 3.0), 2.0);
-        assert_eq!(OperatorType::Times.eval_nums(2.0, 3.0), 6.0);
-        assert_eq!(OperatorType::Divide.eval_nums(6.0, 3.0), 2.0);
+    fn test_whitespace_tokens() {
+        // "1 + 2" with whitespace tokens
+        let token = |value: &str, token_type: TokenType| Token {
+            value: value.to_string(),
+            token_type,
+        };
+        let tokens: TokenList = vec![
+            token("1", TokenType::Number),
+            token(" ", TokenType::Whitespace),
+            token("+", TokenType::Operator),
+            token(" ", TokenType::Whitespace),
+            token("\t", TokenType::Whitespace),
+            token("2", TokenType::Number),
+            token(" ", TokenType::Whitespace),
+        ];
+
+        assert_eq!(
+            tokens_to_rpn(tokens).unwrap(),
+            vec![
+                Value::Number(1.0),
+                Value::Number(2.0),
+                Value::Operator(OperatorType::Plus),
+            ]
+        );
+        assert_eq!(
+            tokens_to_rpn(vec![token(" ", TokenType::Whitespace)]).unwrap(),
+            vec![]
+        );
     }
 
     #[test]
-    fn test_eval_comparison() {
-        assert_eq!(OperatorType::LT.eval_comparison(2.0, 3.0), true);
-        assert_eq!(OperatorType::LT.eval_comparison(5.0, 3.0), false);
-
-        assert_eq!(OperatorType::LE.eval_comparison(3.0, 3.0), true);
-        assert_eq!(OperatorType::LE.eval_comparison(5.0, 3.0), false);
-
-        assert_eq!(OperatorType::GT.eval_comparison(6.0, 3.0), true);
-        assert_eq!(OperatorType::GT.eval_comparison(1.0, 3.0), false);
+    fn test_invalid_number() {
+        let tokens: TokenList = vec![
+            Token {
+                token_type: TokenType::Number,
+                value: "1.2.3".to_string(),
+            },
+            Token {
+                token_type: TokenType::Operator,
+                value: "+".to_string(),
+            },
+            Token {
+                token_type: TokenType::Number,
+                value: "1".to_string(),
+            },
+        ];
 
-        assert_eq!(OperatorType::GE.eval_comparison(3.0, 3.0), true);
-        assert_eq!(OperatorType::GE.eval_comparison(2.0, 3.0), false);
+        let rpn = tokens_to_rpn(tokens);
+        assert_eq!(rpn, Err(ParserError::InvalidNumber("1.2.3".to_string())));
+    }
 
-        assert_eq!(OperatorType::Eq.eval_comparison(3.0, 3.0), true);
-        assert_eq!(OperatorType::Eq.eval_comparison(4.0, 3.0), false);
-        assert_eq!(OperatorType::Eq.eval_comparison(2.0, 3.0), false);
+    #[test]
+    fn test_function() {
+        // "abs(1 - sign(2)) * 3" -> "1 2 sign - abs 3 *"
+        let tokens: TokenList = vec![
+            Token {
+                token_type: TokenType::Identifier,
+                value: "abs".to_string(),
+            },
+            Token {
+                token_type: TokenType::Parenthesis,
+                value: "(".to_string(),
+            },
+            Token {
+                token_type: TokenType::Number,
+                value: "1".to_string(),
+            },
+            Token {
+                token_type: TokenType::Operator,
+                value: "-".to_string(),
+            },
+            Token {
+                token_type: TokenType::Identifier,
+                value: "sign".to_string(),
+            },
+            Token {
+                token_type: TokenType::Parenthesis,
+                value: "(".to_string(),
+            },
+            Token {
+                token_type: TokenType::Number,
+                value: "2".to_string(),
+            },
+            Token {
+                token_type: TokenType::Parenthesis,
+                value: ")".to_string(),
+            },
+            Token {
+                token_type: TokenType::Parenthesis,
+                value: ")".to_string(),
+            },
+            Token {
+                token_type: TokenType::Operator,
+                value: "*".to_string(),
+            },
+            Token {
+                token_type: TokenType::Number,
+                value: "3".to_string(),
+            },
+        ];
+
+        let rpn = tokens_to_rpn(tokens);
+        assert_eq!(
+            rpn.unwrap(),
+            vec![
+                Value::Number(1.0),
+                Value::Number(2.0),
+                Value::Function(FunctionType::Sign, 1),
+                Value::Operator(OperatorType::Minus),
+                Value::Function(FunctionType::Abs, 1),
+                Value::Number(3.0),
+                Value::Operator(OperatorType::Times),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nested_functions() {
+        let parse = |expr: &str| tokens_to_rpn(Tokenizer::new().tokenize(expr).unwrap());
+
+        // "max(min(1, 2), sqrt(9))" -> "1 2 min 9 sqrt max"
+        assert_eq!(
+            parse("max(min(1, 2), sqrt(9))").unwrap(),
+            vec![
+                Value::Number(1.0),
+                Value::Number(2.0),
+                Value::Function(FunctionType::Min, 2),
+                Value::Number(9.0),
+                Value::Function(FunctionType::Sqrt, 1),
+                Value::Function(FunctionType::Max, 2),
+            ]
+        );
+        // "min(1 + max(2, abs(3 - 4)) * 2, 5)" -> "1 2 3 4 - abs max 2 * + 5 min"
+        assert_eq!(
+            parse("min(1 + max(2, abs(3 - 4)) * 2, 5)").unwrap(),
+            vec![
+                Value::Number(1.0),
+                Value::Number(2.0),
+                Value::Number(3.0),
+                Value::Number(4.0),
+                Value::Operator(OperatorType::Minus),
+                Value::Function(FunctionType::Abs, 1),
+                Value::Function(FunctionType::Max, 2),
+                Value::Number(2.0),
+                Value::Operator(OperatorType::Times),
+                Value::Operator(OperatorType::Plus),
+                Value::Number(5.0),
+                Value::Function(FunctionType::Min, 2),
+            ]
+        );
+        assert_eq!(
+            parse("max(min(1, 2, 3), 4)"),
+            Err(ParserError::InvalidArgumentCount {
+                function: FunctionType::Min,
+                found: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_if_function() {
+        let parse = |expr: &str| tokens_to_rpn(Tokenizer::new().tokenize(expr).unwrap());
+
+        // "if(1 > 0, 10, 20)" -> "1 0 > 10 20 if"
+        assert_eq!(
+            parse("if(1 > 0, 10, 20)").unwrap(),
+            vec![
+                Value::Number(1.0),
+                Value::Number(0.0),
+                Value::Operator(OperatorType::GT),
+                Value::Number(10.0),
+                Value::Number(20.0),
+                Value::Function(FunctionType::If, 3),
+            ]
+        );
+        assert_eq!(
+            parse("if(true, 1)"),
+            Err(ParserError::InvalidArgumentCount {
+                function: FunctionType::If,
+                found: 2
+            })
+        );
+        assert_eq!(FunctionType::If.eval_nums(&[0.0, 1.0, 2.0]), 2.0);
+    }
+
+    #[test]
+    fn test_variadic_function() {
+        let parse = |expr: &str| tokens_to_rpn(Tokenizer::new().tokenize(expr).unwrap());
+
+        assert_eq!(
+            parse("first_true(false, 1 > 2, true)").unwrap(),
+            vec![
+                Value::Boolean(false),
+                Value::Number(1.0),
+                Value::Number(2.0),
+                Value::Operator(OperatorType::GT),
+                Value::Boolean(true),
+                Value::Function(FunctionType::FirstTrue, 3),
+            ]
+        );
+        assert_eq!(
+            parse("first_true(true)").unwrap(),
+            vec![
+                Value::Boolean(true),
+                Value::Function(FunctionType::FirstTrue, 1),
+            ]
+        );
+        let err = parse("first_true()").unwrap_err();
+        assert_eq!(
+            err,
+            ParserError::InvalidArgumentCount {
+                function: FunctionType::FirstTrue,
+                found: 0
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "function `first_true` expects at least 1 argument(s), found 0"
+        );
+        assert_eq!(FunctionType::FirstTrue.eval_nums(&[0.0, 1.0, 1.0]), 2.0);
+    }
+
+    #[test]
+    fn test_custom_functions() {
+        let mut functions = FunctionRegistry::new();
+        functions.register("clamp", 3, |args| args[0].clamp(args[1], args[2]));
+        let parser = Parser::new().with_functions(functions);
+        let parse = |expr: &str| parser.parse(Tokenizer::new().tokenize(expr).unwrap());
+
+        let clamp = FunctionType::Custom {
+            name: "clamp".to_string(),
+            arity: 3,
+        };
+        assert_eq!(
+            parse("clamp({x}, 0, 1)").unwrap(),
+            vec![
+                Value::Variable("x".to_string()),
+                Value::Number(0.0),
+                Value::Number(1.0),
+                Value::Function(clamp.clone(), 3),
+            ]
+        );
+        assert_eq!(
+            parse("clamp(1, 2)"),
+            Err(ParserError::InvalidArgumentCount {
+                function: clamp,
+                found: 2
+            })
+        );
+        assert_eq!(
+            parse("foo(1)"),
+            Err(ParserError::UnknownFunction("foo".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_list() {
+        let parse = |expr: &str| tokens_to_rpn(Tokenizer::new().tokenize(expr).unwrap());
+
+        // "(1, 2 + 3, abs(4))" -> "1 2 3 + 4 abs list(3)"
+        assert_eq!(
+            parse("(1, 2 + 3, abs(4))").unwrap(),
+            vec![
+                Value::Number(1.0),
+                Value::Number(2.0),
+                Value::Number(3.0),
+                Value::Operator(OperatorType::Plus),
+                Value::Number(4.0),
+                Value::Function(FunctionType::Abs, 1),
+                Value::List(3),
+            ]
+        );
+        // a single parenthesized value is not a list
+        assert_eq!(parse("(1)").unwrap(), vec![Value::Number(1.0)]);
+    }
+
+    #[test]
+    fn test_function_errors() {
+        let parse = |expr: &str| tokens_to_rpn(Tokenizer::new().tokenize(expr).unwrap());
+
+        assert_eq!(
+            parse("foo(1)"),
+            Err(ParserError::UnknownFunction("foo".to_string()))
+        );
+        assert_eq!(
+            parse("abs + 1"),
+            Err(ParserError::UnknownIdentifier("abs".to_string()))
+        );
+        assert_eq!(
+            parse("abs(1, 2)"),
+            Err(ParserError::InvalidArgumentCount {
+                function: FunctionType::Abs,
+                found: 2
+            })
+        );
+        assert_eq!(
+            parse("sign()"),
+            Err(ParserError::InvalidArgumentCount {
+                function: FunctionType::Sign,
+                found: 0
+            })
+        );
+        assert_eq!(parse("1, 2"), Err(ParserError::SeparatorOutsideFunction));
+    }
+
+    #[test]
+    fn test_consecutive_operators() {
+        let parse = |expr: &str| tokens_to_rpn(Tokenizer::new().tokenize(expr).unwrap());
+
+        assert_eq!(
+            parse("1 + * 2"),
+            Err(ParserError::UnexpectedOperator(OperatorType::Times))
+        );
+        assert_eq!(
+            parse("1 + + 2"),
+            Err(ParserError::UnexpectedOperator(OperatorType::Plus))
+        );
+        assert_eq!(
+            parse("1 * / 2"),
+            Err(ParserError::UnexpectedOperator(OperatorType::Divide))
+        );
+        assert!(parse("1 + (2 * 3)").is_ok());
+    }
+
+    #[test]
+    fn test_unbalanced_parentheses() {
+        let parse = |expr: &str| tokens_to_rpn(Tokenizer::new().tokenize(expr).unwrap());
+
+        assert_eq!(
+            parse("((1)"),
+            Err(ParserError::UnbalancedParentheses { open: 1, close: 0 })
+        );
+        assert_eq!(
+            parse("(1))"),
+            Err(ParserError::UnbalancedParentheses { open: 0, close: 1 })
+        );
+        assert_eq!(
+            parse(")1 + (2"),
+            Err(ParserError::UnbalancedParentheses { open: 1, close: 1 })
+        );
+        assert_eq!(
+            parse("abs((((1)"),
+            Err(ParserError::UnbalancedParentheses { open: 3, close: 0 })
+        );
+    }
+
+    #[test]
+    fn test_missing_operand() {
+        let parse = |expr: &str| tokens_to_rpn(Tokenizer::new().tokenize(expr).unwrap());
+
+        assert_eq!(
+            parse("+ 1"),
+            Err(ParserError::MissingOperand(OperatorType::Plus))
+        );
+        assert_eq!(
+            parse("1 +"),
+            Err(ParserError::MissingOperand(OperatorType::Plus))
+        );
+        assert_eq!(
+            parse("2 * (1 -)"),
+            Err(ParserError::MissingOperand(OperatorType::Minus))
+        );
+        assert_eq!(
+            parse("(* 2)"),
+            Err(ParserError::MissingOperand(OperatorType::Times))
+        );
+        assert_eq!(
+            parse("abs(1 <)"),
+            Err(ParserError::MissingOperand(OperatorType::LT))
+        );
+        assert_eq!(parse("1 + ()"), Err(ParserError::EmptyParentheses));
+        assert_eq!(parse("() * 2"), Err(ParserError::EmptyParentheses));
+        assert_eq!(parse("max(())"), Err(ParserError::EmptyParentheses));
+
+        assert_eq!(parse("(1,)"), Err(ParserError::MissingElement));
+        assert_eq!(parse("(,1)"), Err(ParserError::MissingElement));
+        assert_eq!(parse("max(1,)"), Err(ParserError::MissingElement));
+        assert_eq!(parse("max(1,,2)"), Err(ParserError::MissingElement));
+        assert_eq!(parse("2 in (1, 2,)"), Err(ParserError::MissingElement));
+    }
+
+    #[test]
+    fn test_missing_operator() {
+        let parse = |expr: &str| tokens_to_rpn(Tokenizer::new().tokenize(expr).unwrap());
+
+        assert_eq!(
+            parse("1 2"),
+            Err(ParserError::MissingOperator("2".to_string()))
+        );
+        assert_eq!(
+            parse("(1 + 2) {x}"),
+            Err(ParserError::MissingOperator("x".to_string()))
+        );
+        assert_eq!(
+            parse("2 (3)"),
+            Err(ParserError::MissingOperator("(".to_string()))
+        );
+        assert_eq!(
+            parse("3! abs(1)"),
+            Err(ParserError::MissingOperator("abs".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_factorial() {
+        let parse = |expr: &str| tokens_to_rpn(Tokenizer::new().tokenize(expr).unwrap());
+
+        // "2 * 3! + 1" -> "2 3 ! * 1 +"
+        assert_eq!(
+            parse("2 * 3! + 1").unwrap(),
+            vec![
+                Value::Number(2.0),
+                Value::Number(3.0),
+                Value::Operator(OperatorType::Factorial),
+                Value::Operator(OperatorType::Times),
+                Value::Number(1.0),
+                Value::Operator(OperatorType::Plus),
+            ]
+        );
+        assert_eq!(
+            parse("(2 + 1)!").unwrap(),
+            vec![
+                Value::Number(2.0),
+                Value::Number(1.0),
+                Value::Operator(OperatorType::Plus),
+                Value::Operator(OperatorType::Factorial),
+            ]
+        );
+        assert_eq!(
+            parse("1 + !"),
+            Err(ParserError::UnexpectedOperator(OperatorType::Factorial))
+        );
+        assert_eq!(
+            parse("!1"),
+            Err(ParserError::UnexpectedOperator(OperatorType::Factorial))
+        );
+    }
+
+    #[test]
+    fn test_eval_nums() {
+        assert_eq!(OperatorType::Plus.eval_nums(2.0, 3.0), 5.0);
+        assert_eq!(OperatorType::Minus.eval_nums(5.0, 3.0), 2.0);
+        assert_eq!(OperatorType::Times.eval_nums(2.0, 3.0), 6.0);
+        assert_eq!(OperatorType::Divide.eval_nums(6.0, 3.0), 2.0);
+        assert_eq!(OperatorType::BitAnd.eval_nums(12.0, 10.0), 8.0);
+    }
+
+    #[test]
+    fn test_eval_comparison() {
+        assert_eq!(OperatorType::LT.eval_comparison(2.0, 3.0), true);
+        assert_eq!(OperatorType::LT.eval_comparison(5.0, 3.0), false);
+
+        assert_eq!(OperatorType::LE.eval_comparison(3.0, 3.0), true);
+        assert_eq!(OperatorType::LE.eval_comparison(5.0, 3.0), false);
+
+        assert_eq!(OperatorType::GT.eval_comparison(6.0, 3.0), true);
+        assert_eq!(OperatorType::GT.eval_comparison(1.0, 3.0), false);
+
+        assert_eq!(OperatorType::GE.eval_comparison(3.0, 3.0), true);
+        assert_eq!(OperatorType::GE.eval_comparison(2.0, 3.0), false);
+
+        assert_eq!(OperatorType::Eq.eval_comparison(3.0, 3.0), true);
+        assert_eq!(OperatorType::Eq.eval_comparison(4.0, 3.0), false);
+        assert_eq!(OperatorType::Eq.eval_comparison(2.0, 3.0), false);
+        assert_eq!(OperatorType::Eq.eval_comparison(f32::NAN, f32::NAN), false);
+        assert_eq!(OperatorType::LE.eval_comparison(f32::NAN, 1.0), false);
+    }
+
+    #[test]
+    fn test_placeholders() {
+        let parse = |expr: &str| tokens_to_rpn(Tokenizer::new().tokenize(expr).unwrap());
+
+        assert_eq!(
+            parse("? + ? * ?").unwrap(),
+            vec![
+                Value::Placeholder(0),
+                Value::Placeholder(1),
+                Value::Placeholder(2),
+                Value::Operator(OperatorType::Times),
+                Value::Operator(OperatorType::Plus),
+            ]
+        );
+        assert_eq!(
+            parse("? ?"),
+            Err(ParserError::MissingOperator("1".to_string()))
+        );
+
+        // the tokenizer numbers the placeholders, a hand-built token may not
+        let tokens: TokenList = vec![Token {
+            value: "?".to_string(),
+            token_type: TokenType::Placeholder,
+        }];
+        assert_eq!(
+            tokens_to_rpn(tokens),
+            Err(ParserError::InvalidPlaceholder("?".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_unknown_operator() {
+        // the symbol is tokenized but has no definition in the parser's operator table
+        let tokens = Tokenizer::new()
+            .with_operator_symbols(HashSet::from(["%%".to_string()]))
+            .tokenize("1 %% 2")
+            .unwrap();
+        assert_eq!(
+            tokens_to_rpn(tokens),
+            Err(ParserError::UnknownOperator("%%".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_coalesce() {
+        let parse = |expr: &str| tokens_to_rpn(Tokenizer::new().tokenize(expr).unwrap());
+
+        // lowest priority: "{x} ?? 1 + 2 || false" -> "x 1 2 + false || ??"
+        assert_eq!(
+            parse("{x} ?? 1 + 2 || false").unwrap(),
+            vec![
+                Value::Variable("x".to_string()),
+                Value::Number(1.0),
+                Value::Number(2.0),
+                Value::Operator(OperatorType::Plus),
+                Value::Boolean(false),
+                Value::Operator(OperatorType::Or),
+                Value::Operator(OperatorType::Coalesce),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_in() {
+        let parse = |expr: &str| tokens_to_rpn(Tokenizer::new().tokenize(expr).unwrap());
+
+        // "{x} + 1 in (1, 2) && true" -> "x 1 + 1 2 (2) in true &&"
+        assert_eq!(
+            parse("{x} + 1 in (1, 2) && true").unwrap(),
+            vec![
+                Value::Variable("x".to_string()),
+                Value::Number(1.0),
+                Value::Operator(OperatorType::Plus),
+                Value::Number(1.0),
+                Value::Number(2.0),
+                Value::List(2),
+                Value::Operator(OperatorType::In),
+                Value::Boolean(true),
+                Value::Operator(OperatorType::And),
+            ]
+        );
+        // only a whole word is an operator
+        assert_eq!(
+            parse("inf"),
+            Err(ParserError::UnknownIdentifier("inf".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_macros() {
+        let double = tokens_to_rpn(Tokenizer::new().tokenize("{x} + {x}").unwrap()).unwrap();
+        let parser = Parser::new().with_macros(HashMap::from([("double".to_string(), double)]));
+        let parse = |expr: &str| parser.parse(Tokenizer::new().tokenize(expr).unwrap());
+
+        assert_eq!(
+            parse("double * 2"),
+            tokens_to_rpn(Tokenizer::new().tokenize("({x} + {x}) * 2").unwrap())
+        );
+        assert_eq!(
+            parse("triple * 2"),
+            Err(ParserError::UnknownIdentifier("triple".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_implicit_multiplication() {
+        let parse =
+            |parser: &Parser, expr: &str| parser.parse(Tokenizer::new().tokenize(expr).unwrap());
+        let strict = Parser::new();
+        let implicit = Parser::new().with_implicit_multiplication(true);
+
+        assert_eq!(
+            parse(&strict, "2{x}"),
+            Err(ParserError::MissingOperator("x".to_string()))
+        );
+        assert_eq!(
+            parse(&strict, "3(1+1)"),
+            Err(ParserError::MissingOperator("(".to_string()))
+        );
+
+        assert_eq!(parse(&implicit, "2{x}"), parse(&strict, "2 * {x}"));
+        assert_eq!(parse(&implicit, "3(1+1)"), parse(&strict, "3 * (1+1)"));
+        assert_eq!(
+            parse(&implicit, "1 + 2{x}{y}"),
+            parse(&strict, "1 + 2 * {x} * {y}")
+        );
+        // only variables and parentheses can follow an operand
+        assert_eq!(
+            parse(&implicit, "{x}2"),
+            Err(ParserError::MissingOperator("2".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_priority() {
+        let priority = |op: OperatorType| op.priority().unwrap();
+
+        assert!(priority(OperatorType::Times) > priority(OperatorType::Plus));
+        assert!(priority(OperatorType::Plus) > priority(OperatorType::And));
+        assert!(priority(OperatorType::And) > priority(OperatorType::Or));
+        assert!(priority(OperatorType::Or) > priority(OperatorType::Implies));
+        assert_eq!(
+            OperatorType::Minus.priority(),
+            OperatorType::Plus.priority()
+        );
+        assert_eq!(OperatorType::LeftParenthesis.priority(), None);
+    }
+
+    #[test]
+    fn test_try_eval() {
+        let num = EmitResult::Number;
+        let boolean = EmitResult::Boolean;
+
+        assert_eq!(
+            OperatorType::Times.try_eval(num(3.0), num(4.0)),
+            Ok(num(12.0))
+        );
+        assert_eq!(
+            OperatorType::BitAnd.try_eval(num(6.0), num(3.0)),
+            Ok(num(2.0))
+        );
+        assert_eq!(
+            OperatorType::LE.try_eval(num(1.0), num(1.0)),
+            Ok(boolean(true))
+        );
+        assert_eq!(
+            OperatorType::Or.try_eval(boolean(false), boolean(true)),
+            Ok(boolean(true))
+        );
+        assert_eq!(
+            OperatorType::Coalesce.try_eval(num(1.0), num(2.0)),
+            Ok(num(1.0))
+        );
+
+        assert_eq!(
+            OperatorType::Plus.try_eval(num(1.0), boolean(true)),
+            Err(EvalError::TypeMismatch {
+                operator: OperatorType::Plus,
+                left: VarType::Number,
+                right: VarType::Boolean,
+            })
+        );
+        assert_eq!(
+            OperatorType::And.try_eval(num(1.0), num(1.0)),
+            Err(EvalError::TypeMismatch {
+                operator: OperatorType::And,
+                left: VarType::Number,
+                right: VarType::Number,
+            })
+        );
+        assert_eq!(
+            OperatorType::Factorial.try_eval(num(3.0), num(3.0)),
+            Err(EvalError::NotBinaryOperator(OperatorType::Factorial))
+        );
+    }
+
+    #[test]
+    fn test_eval_postfix() {
+        assert_eq!(OperatorType::Factorial.eval_postfix(5.0), Some(120.0));
+        assert_eq!(OperatorType::Factorial.eval_postfix(0.0), Some(1.0));
+        assert_eq!(OperatorType::Factorial.eval_postfix(2.5), None);
+        assert_eq!(OperatorType::Factorial.eval_postfix(-1.0), None);
+        assert_eq!(
+            OperatorType::Factorial.eval_postfix(1000.0),
+            Some(f32::INFINITY)
+        );
     }
 
     #[test]
@@ -1,12 +1,22 @@
 use regex::Regex;
+use std::collections::HashSet;
 
-const WHITESPACE_REGEX: &str = r"^\s+";
+// `\s` is Unicode-aware, so it includes non-breaking spaces and `\r\n`
+const WHITESPACE_REGEX: &str = r"^(\s+)";
 // "unregognized escape sequence" - on `\/` (division)
-const OPERATOR_REGEX: &str = r"^(\+|-|\*|\/|&&|\|\||<=|>=|<|>|=)";
-const NUMBER_REGEX: &str = r"^(\d+(?:\.\d+)?)";
-const BOOLEAN_REGEX: &str = r"^(true|false)";
+const OPERATOR_REGEX: &str = r"^(\+|-|\*|\/|&&|&|\|\||\?\?|<=|>=|<|>|=>|==|=|!|in\b)";
+const HEX_NUMBER_REGEX: &str = r"^(0x[0-9A-Fa-f]+)";
+const BINARY_NUMBER_REGEX: &str = r"^(0b[01]+)";
+// digits can be grouped by single underscores, e.g. `1_000`, `.5` is `0.5`, `3.` is rejected
+const NUMBER_REGEX: &str = r"^(\d+(?:_\d+)*(?:\.\d+)?|\.\d+)";
+const DECIMAL_COMMA_NUMBER_REGEX: &str = r"^(\d+(?:_\d+)*(?:,\d+)?)";
+const BOOLEAN_REGEX: &str = r"^(true|false)\b";
 const VARIABLE_REGEX: &str = r"^\{(.+?)\}";
 const PARENTHESIS_REGEX: &str = r"^(\(|\))";
+const IDENTIFIER_REGEX: &str = r"^([A-Za-z][A-Za-z0-9_]*)";
+const COMMA_REGEX: &str = r"^(,)";
+// matched after the operators, so `??` stays an operator
+const PLACEHOLDER_REGEX: &str = r"^(\?)";
 
 /// each member contains a regex match
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -17,6 +27,14 @@ pub enum TokenType {
     Boolean,
     Variable,
     Parenthesis,
+    /// constant or unknown function name
+    Identifier,
+    /// identifier which is one of the tokenizer's function names
+    Function,
+    /// function argument separator
+    Comma,
+    /// `?`, the value is the index among the placeholders, starting at 0
+    Placeholder,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -27,6 +45,63 @@ pub struct Token {
 
 pub type TokenList = Vec<Token>;
 
+/// Byte range of a token or an error in the expression string.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// Renders `message` followed by the line of `source` containing the span,
+    /// with the span underlined by carets.
+    pub fn report(&self, source: &str, message: &str) -> String {
+        let start = self.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |index| index + 1);
+        let line_end = source[start..]
+            .find(['\r', '\n'])
+            .map_or(source.len(), |index| start + index);
+        let line_number = source[..start].matches('\n').count() + 1;
+
+        let column = source[line_start..start].chars().count();
+        let width = source[start..self.end.clamp(start, line_end)]
+            .chars()
+            .count()
+            .max(1);
+        let gutter = " ".repeat(line_number.to_string().len());
+
+        format!(
+            "error: {message}\n{gutter} |\n{line_number} | {}\n{gutter} | {}{}",
+            &source[line_start..line_end],
+            " ".repeat(column),
+            "^".repeat(width)
+        )
+    }
+}
+
+/// Short, readable dump of the tokens, e.g. `NUM(4) OP(/) NUM(2)`.
+pub fn describe_tokens(tokens: &TokenList) -> String {
+    tokens
+        .iter()
+        .map(|token| {
+            let kind = match token.token_type {
+                TokenType::Whitespace => "WS",
+                TokenType::Operator => "OP",
+                TokenType::Number => "NUM",
+                TokenType::Boolean => "BOOL",
+                TokenType::Variable => "VAR",
+                TokenType::Parenthesis => "PAREN",
+                TokenType::Identifier => "ID",
+                TokenType::Function => "FN",
+                TokenType::Comma => "COMMA",
+                TokenType::Placeholder => "PH",
+            };
+            format!("{kind}({})", token.value)
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
 struct Matcher {
     regex: Regex,
     token_type: TokenType,
@@ -34,6 +109,8 @@ struct Matcher {
 
 pub struct Tokenizer {
     matchers: Vec<Matcher>,
+    keep_whitespace: bool,
+    function_names: HashSet<String>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -42,6 +119,24 @@ pub enum TokenizerError {
     NoTokenMatched(String),
 }
 
+impl TokenizerError {
+    /// position of the error in `source`, the string which failed to tokenize
+    pub fn span(&self, source: &str) -> Span {
+        match self {
+            TokenizerError::NoTokenMatched(substr) => {
+                let start = source.len().saturating_sub(substr.len());
+                let end = start + substr.chars().next().map_or(0, char::len_utf8);
+                Span { start, end }
+            }
+        }
+    }
+
+    /// Renders the error with the `source` line and the unmatched character underlined.
+    pub fn reported(&self, source: &str) -> String {
+        self.span(source).report(source, &self.to_string())
+    }
+}
+
 impl std::fmt::Display for TokenizerError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -54,6 +149,12 @@ impl std::fmt::Display for TokenizerError {
     }
 }
 
+impl Default for Tokenizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Tokenizer {
     pub fn new() -> Self {
         Self {
@@ -63,11 +164,31 @@ impl Tokenizer {
                         .expect("error while creating whitespace matcher regex"),
                     token_type: TokenType::Whitespace,
                 },
+                Matcher {
+                    regex: Regex::new(VARIABLE_REGEX)
+                        .expect("error while creating variable matcher regex"),
+                    token_type: TokenType::Variable,
+                },
                 Matcher {
                     regex: Regex::new(OPERATOR_REGEX)
                         .expect("error while creating operator matcher regex"),
                     token_type: TokenType::Operator,
                 },
+                Matcher {
+                    regex: Regex::new(PLACEHOLDER_REGEX)
+                        .expect("error while creating placeholder matcher regex"),
+                    token_type: TokenType::Placeholder,
+                },
+                Matcher {
+                    regex: Regex::new(HEX_NUMBER_REGEX)
+                        .expect("error while creating hexadecimal number matcher regex"),
+                    token_type: TokenType::Number,
+                },
+                Matcher {
+                    regex: Regex::new(BINARY_NUMBER_REGEX)
+                        .expect("error while creating binary number matcher regex"),
+                    token_type: TokenType::Number,
+                },
                 Matcher {
                     regex: Regex::new(NUMBER_REGEX)
                         .expect("error while creating number matcher regex"),
@@ -78,24 +199,134 @@ impl Tokenizer {
                         .expect("error while creating boolean matcher regex"),
                     token_type: TokenType::Boolean,
                 },
-                Matcher {
-                    regex: Regex::new(VARIABLE_REGEX)
-                        .expect("error while creating variable matcher regex"),
-                    token_type: TokenType::Variable,
-                },
                 Matcher {
                     regex: Regex::new(PARENTHESIS_REGEX)
                         .expect("error while creating parenthesis matcher regex"),
                     token_type: TokenType::Parenthesis,
                 },
+                Matcher {
+                    regex: Regex::new(IDENTIFIER_REGEX)
+                        .expect("error while creating identifier matcher regex"),
+                    token_type: TokenType::Identifier,
+                },
+                Matcher {
+                    regex: Regex::new(COMMA_REGEX)
+                        .expect("error while creating comma matcher regex"),
+                    token_type: TokenType::Comma,
+                },
             ],
+            keep_whitespace: false,
+            function_names: HashSet::new(),
         }
     }
 
+    /// Keeps whitespace in the token list, e.g. for formatting.
+    /// The parser skips whitespace tokens.
+    pub fn with_whitespace(mut self, keep: bool) -> Self {
+        self.keep_whitespace = keep;
+        self
+    }
+
+    /// Identifiers with these names are tokenized as [`TokenType::Function`].
+    pub fn with_function_names(mut self, names: HashSet<String>) -> Self {
+        self.function_names = names;
+        self
+    }
+
+    /// Tokenizes these symbols as [`TokenType::Operator`], for the custom operators
+    /// of [`crate::Parser::with_operators`]. They are matched before the built-in operators,
+    /// longer symbols first.
+    pub fn with_operator_symbols(mut self, symbols: HashSet<String>) -> Self {
+        if symbols.is_empty() {
+            return self;
+        }
+        let mut symbols: Vec<String> = symbols.iter().map(|symbol| regex::escape(symbol)).collect();
+        symbols.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+        let matcher = Matcher {
+            regex: Regex::new(&format!("^({})", symbols.join("|")))
+                .expect("error while creating custom operator matcher regex"),
+            token_type: TokenType::Operator,
+        };
+
+        let position = self
+            .matchers
+            .iter()
+            .position(|matcher| matcher.token_type == TokenType::Operator)
+            .unwrap_or(self.matchers.len());
+        self.matchers.insert(position, matcher);
+        self
+    }
+
+    /// Replaces the default `{` and `}` around variable names.
+    /// Variables are matched before any other token, so the delimiters can overlap with operators.
+    pub fn with_variable_delimiters(mut self, open: &str, close: &str) -> Self {
+        let regex = Regex::new(&format!(
+            "^{}(.+?){}",
+            regex::escape(open),
+            regex::escape(close)
+        ))
+        .expect("error while creating variable matcher regex");
+
+        for matcher in self
+            .matchers
+            .iter_mut()
+            .filter(|matcher| matcher.token_type == TokenType::Variable)
+        {
+            matcher.regex = regex.clone();
+        }
+
+        self
+    }
+
+    /// Uses `,` as the decimal separator, e.g. `1,5`.
+    /// The comma cannot separate function arguments at the same time,
+    /// so only single-argument functions can be called in this mode.
+    pub fn with_decimal_comma(mut self) -> Self {
+        let regex = Regex::new(DECIMAL_COMMA_NUMBER_REGEX)
+            .expect("error while creating number matcher regex");
+
+        self.matchers
+            .retain(|matcher| matcher.token_type != TokenType::Comma);
+        for matcher in self
+            .matchers
+            .iter_mut()
+            .filter(|matcher| matcher.regex.as_str() == NUMBER_REGEX)
+        {
+            matcher.regex = regex.clone();
+        }
+
+        self
+    }
+
     pub fn tokenize(&self, str: &str) -> Result<TokenList, TokenizerError> {
-        let mut tokens: TokenList = vec![];
+        let mut tokens = vec![];
+        self.tokenize_into(str, &mut tokens)?;
+        Ok(tokens)
+    }
+
+    /// Same as `tokenize`, but clears `out` and pushes the tokens to it,
+    /// reusing its allocation when tokenizing many strings.
+    /// On error `out` contains the tokens before the unmatched part.
+    pub fn tokenize_into(&self, str: &str, out: &mut TokenList) -> Result<(), TokenizerError> {
+        out.clear();
+        self.tokenize_each(str, |token, _| out.push(token))
+    }
+
+    /// Tokenizes the string, returning the position of every token in it as well.
+    pub fn tokenize_spanned(&self, str: &str) -> Result<Vec<(Token, Span)>, TokenizerError> {
+        let mut tokens = vec![];
+        self.tokenize_each(str, |token, span| tokens.push((token, span)))?;
+        Ok(tokens)
+    }
 
+    // calls `push` with every token in order
+    fn tokenize_each(
+        &self,
+        str: &str,
+        mut push: impl FnMut(Token, Span),
+    ) -> Result<(), TokenizerError> {
         let mut index = 0;
+        let mut placeholders = 0;
         while index < str.len() {
             let (match_type, captures) = self
                 .matchers
@@ -109,32 +340,50 @@ impl Tokenizer {
 
                     None
                 })
-                .ok_or(TokenizerError::NoTokenMatched(str[index..].to_owned()))?;
+                .ok_or_else(|| TokenizerError::NoTokenMatched(str[index..].to_owned()))?;
 
-            index += captures
+            // a zero-length match would never advance the index
+            let whole_match = captures
                 .get(0)
-                .expect("couldn't get 0th capture group")
-                .len();
-            if match_type != TokenType::Whitespace {
-                tokens.push(Token {
-                    value: captures
-                        .get(1)
-                        .expect("couldn't get 1th group (should be present in every regex)")
-                        .as_str()
-                        .to_string(),
-                    token_type: match_type,
-                });
+                .filter(|m| !m.is_empty())
+                .ok_or_else(|| TokenizerError::NoTokenMatched(str[index..].to_owned()))?;
+
+            if match_type != TokenType::Whitespace || self.keep_whitespace {
+                let mut value = captures
+                    .get(1)
+                    .ok_or_else(|| TokenizerError::NoTokenMatched(str[index..].to_owned()))?
+                    .as_str()
+                    .to_string();
+                if match_type == TokenType::Placeholder {
+                    value = placeholders.to_string();
+                    placeholders += 1;
+                }
+                let token_type = match match_type {
+                    TokenType::Identifier if self.function_names.contains(&value) => {
+                        TokenType::Function
+                    }
+                    _ => match_type,
+                };
+                let span = Span {
+                    start: index,
+                    end: index + whole_match.len(),
+                };
+                push(Token { value, token_type }, span);
             }
+            index += whole_match.len();
         }
 
-        Ok(tokens)
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Token, TokenType, Tokenizer};
+    use super::{describe_tokens, Span, Token, TokenType, Tokenizer};
+    use crate::parser::{tokens_to_rpn, OperatorType, Value};
     use pretty_assertions::assert_eq;
+    use proptest::prelude::*;
+    use std::collections::HashSet;
 
     #[test]
     fn test_tokenizer() {
@@ -303,4 +552,329 @@ mod tests {
             ]
         )
     }
+
+    #[test]
+    fn test_tokenizer_functions() {
+        let tokens = Tokenizer::new().tokenize("abs({x}, 2)");
+        assert_eq!(
+            tokens.unwrap(),
+            vec![
+                Token {
+                    token_type: TokenType::Identifier,
+                    value: "abs".to_string()
+                },
+                Token {
+                    token_type: TokenType::Parenthesis,
+                    value: "(".to_string()
+                },
+                Token {
+                    token_type: TokenType::Variable,
+                    value: "x".to_string()
+                },
+                Token {
+                    token_type: TokenType::Comma,
+                    value: ",".to_string()
+                },
+                Token {
+                    token_type: TokenType::Number,
+                    value: "2".to_string()
+                },
+                Token {
+                    token_type: TokenType::Parenthesis,
+                    value: ")".to_string()
+                },
+            ]
+        );
+
+        let tokens = Tokenizer::new().tokenize("trueish");
+        assert_eq!(
+            tokens.unwrap(),
+            vec![Token {
+                token_type: TokenType::Identifier,
+                value: "trueish".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_describe_tokens() {
+        let tokens = Tokenizer::new().tokenize("4/2").unwrap();
+        assert_eq!(describe_tokens(&tokens), "NUM(4) OP(/) NUM(2)");
+
+        let tokens = Tokenizer::new().tokenize("abs({x}, true)").unwrap();
+        assert_eq!(
+            describe_tokens(&tokens),
+            "ID(abs) PAREN(() VAR(x) COMMA(,) BOOL(true) PAREN())"
+        );
+    }
+
+    #[test]
+    fn test_variable_delimiters() {
+        let tokenizer = Tokenizer::new().with_variable_delimiters("<<", ">>");
+        let tokens = tokenizer.tokenize("<<x>> < 2");
+        assert_eq!(
+            tokens.unwrap(),
+            vec![
+                Token {
+                    token_type: TokenType::Variable,
+                    value: "x".to_string()
+                },
+                Token {
+                    token_type: TokenType::Operator,
+                    value: "<".to_string()
+                },
+                Token {
+                    token_type: TokenType::Number,
+                    value: "2".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keep_whitespace() {
+        let tokenizer = Tokenizer::new().with_whitespace(true);
+        let tokens = tokenizer.tokenize(" abs( {x} )\t+ 1").unwrap();
+        assert_eq!(
+            describe_tokens(&tokens),
+            "WS( ) ID(abs) PAREN(() WS( ) VAR(x) WS( ) PAREN()) WS(\t) OP(+) WS( ) NUM(1)"
+        );
+        assert_eq!(
+            tokens_to_rpn(tokens).unwrap(),
+            tokens_to_rpn(Tokenizer::new().tokenize("abs({x}) + 1").unwrap()).unwrap()
+        );
+
+        assert_eq!(
+            describe_tokens(&Tokenizer::new().tokenize(" 1 ").unwrap()),
+            "NUM(1)"
+        );
+    }
+
+    #[test]
+    fn test_unicode_whitespace() {
+        let tokenizer = Tokenizer::new();
+        assert_eq!(
+            describe_tokens(&tokenizer.tokenize("1\u{00A0}+\u{00A0}2").unwrap()),
+            "NUM(1) OP(+) NUM(2)"
+        );
+        assert_eq!(
+            describe_tokens(&tokenizer.tokenize("1 +\r\n\u{2003}2\r\n").unwrap()),
+            "NUM(1) OP(+) NUM(2)"
+        );
+    }
+
+    #[test]
+    fn test_reported() {
+        let source = "1 + @";
+        let err = Tokenizer::new().tokenize(source).unwrap_err();
+        assert_eq!(err.span(source), Span { start: 4, end: 5 });
+        assert_eq!(
+            err.reported(source),
+            "error: token not matched at the beginning of substring: '@'\n  |\n1 | 1 + @\n  |     ^"
+        );
+
+        let source = "1 +\n2 ~ 3";
+        let err = Tokenizer::new().tokenize(source).unwrap_err();
+        assert_eq!(
+            err.reported(source),
+            "error: token not matched at the beginning of substring: '~ 3'\n  |\n2 | 2 ~ 3\n  |   ^"
+        );
+    }
+
+    #[test]
+    fn test_tokenize_spanned() {
+        let tokens = Tokenizer::new().tokenize_spanned("12 + {x}").unwrap();
+        let spans: Vec<Span> = tokens.into_iter().map(|(_, span)| span).collect();
+        assert_eq!(
+            spans,
+            vec![
+                Span { start: 0, end: 2 },
+                Span { start: 3, end: 4 },
+                Span { start: 5, end: 8 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_into() {
+        let tokenizer = Tokenizer::new();
+        let mut tokens = vec![];
+
+        tokenizer.tokenize_into("1 + {x} * 2", &mut tokens).unwrap();
+        assert_eq!(describe_tokens(&tokens), "NUM(1) OP(+) VAR(x) OP(*) NUM(2)");
+        tokenizer.tokenize_into("true", &mut tokens).unwrap();
+        assert_eq!(describe_tokens(&tokens), "BOOL(true)");
+        tokenizer.tokenize_into("(3)", &mut tokens).unwrap();
+        assert_eq!(tokens, tokenizer.tokenize("(3)").unwrap());
+
+        assert!(tokenizer.tokenize_into("1 + #", &mut tokens).is_err());
+        assert_eq!(describe_tokens(&tokens), "NUM(1) OP(+)");
+    }
+
+    #[test]
+    fn test_function_names() {
+        let tokenizer = Tokenizer::new().with_function_names(HashSet::from(["max".to_string()]));
+        let tokens = tokenizer.tokenize("max(1, 2)").unwrap();
+        assert_eq!(
+            describe_tokens(&tokens),
+            "FN(max) PAREN(() NUM(1) COMMA(,) NUM(2) PAREN())"
+        );
+        assert_eq!(
+            tokens_to_rpn(tokens).unwrap(),
+            tokens_to_rpn(Tokenizer::new().tokenize("max(1, 2)").unwrap()).unwrap()
+        );
+
+        assert_eq!(
+            describe_tokens(&tokenizer.tokenize("min(1, 2)").unwrap()),
+            "ID(min) PAREN(() NUM(1) COMMA(,) NUM(2) PAREN())"
+        );
+    }
+
+    #[test]
+    fn test_digit_separators() {
+        let tokenizer = Tokenizer::new();
+        let number = |expr: &str| tokens_to_rpn(tokenizer.tokenize(expr).unwrap()).unwrap();
+
+        assert_eq!(
+            describe_tokens(&tokenizer.tokenize("1_000 + 2").unwrap()),
+            "NUM(1_000) OP(+) NUM(2)"
+        );
+        assert_eq!(number("1_000"), vec![Value::Number(1000.0)]);
+        assert_eq!(number("1_000_000"), vec![Value::Number(1000000.0)]);
+        assert_eq!(number("1_000.25"), vec![Value::Number(1000.25)]);
+
+        assert!(tokenizer.tokenize("1__0").is_err());
+        assert!(tokenizer.tokenize("1_").is_err());
+        assert!(tokenizer.tokenize("_1").is_err());
+    }
+
+    #[test]
+    fn test_leading_decimal_point() {
+        let tokenizer = Tokenizer::new();
+        let number = |expr: &str| tokens_to_rpn(tokenizer.tokenize(expr).unwrap()).unwrap();
+
+        assert_eq!(
+            describe_tokens(&tokenizer.tokenize(".5").unwrap()),
+            "NUM(.5)"
+        );
+        assert_eq!(number(".5"), vec![Value::Number(0.5)]);
+        assert_eq!(
+            describe_tokens(&tokenizer.tokenize("2 + .25").unwrap()),
+            "NUM(2) OP(+) NUM(.25)"
+        );
+
+        assert!(tokenizer.tokenize("3.").is_err());
+        assert!(tokenizer.tokenize(".").is_err());
+        assert!(tokenizer.tokenize("._5").is_err());
+    }
+
+    #[test]
+    fn test_double_equals() {
+        let tokenizer = Tokenizer::new();
+        assert_eq!(
+            describe_tokens(&tokenizer.tokenize("1 == 1").unwrap()),
+            "NUM(1) OP(==) NUM(1)"
+        );
+        assert_eq!(
+            describe_tokens(&tokenizer.tokenize("{x}={y}").unwrap()),
+            "VAR(x) OP(=) VAR(y)"
+        );
+    }
+
+    #[test]
+    fn test_placeholders() {
+        assert_eq!(
+            describe_tokens(&Tokenizer::new().tokenize("? + ?*{x} ?? ?").unwrap()),
+            "PH(0) OP(+) PH(1) OP(*) VAR(x) OP(??) PH(2)"
+        );
+    }
+
+    #[test]
+    fn test_implies() {
+        assert_eq!(
+            describe_tokens(&Tokenizer::new().tokenize("{a}=>{b} >= 1").unwrap()),
+            "VAR(a) OP(=>) VAR(b) OP(>=) NUM(1)"
+        );
+    }
+
+    #[test]
+    fn test_decimal_comma() {
+        let tokenizer = Tokenizer::new().with_decimal_comma();
+
+        assert_eq!(
+            describe_tokens(&tokenizer.tokenize("1,5 + 2,5").unwrap()),
+            "NUM(1,5) OP(+) NUM(2,5)"
+        );
+        assert_eq!(
+            describe_tokens(&tokenizer.tokenize("abs(1,5)").unwrap()),
+            "ID(abs) PAREN(() NUM(1,5) PAREN())"
+        );
+        // a trailing comma is not part of the number, and not a separator either
+        assert!(tokenizer.tokenize("1,").is_err());
+
+        let rpn = tokens_to_rpn(tokenizer.tokenize("1,5 + 2,5").unwrap()).unwrap();
+        assert_eq!(
+            rpn,
+            vec![
+                Value::Number(1.5),
+                Value::Number(2.5),
+                Value::Operator(OperatorType::Plus),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_operator_symbols() {
+        let tokenizer = Tokenizer::new().with_operator_symbols(HashSet::from([
+            "%%".to_string(),
+            "**".to_string(),
+            "<=>".to_string(),
+        ]));
+
+        assert_eq!(
+            describe_tokens(&tokenizer.tokenize("4 %% 6**{x} <=> 1 <= 2*3").unwrap()),
+            "NUM(4) OP(%%) NUM(6) OP(**) VAR(x) OP(<=>) NUM(1) OP(<=) NUM(2) OP(*) NUM(3)"
+        );
+        assert!(Tokenizer::new().tokenize("4 %% 6").is_err());
+    }
+
+    #[test]
+    fn test_tokenizer_radix_numbers() {
+        let tokens = Tokenizer::new().tokenize("0xFF & 0b1010");
+        assert_eq!(
+            tokens.unwrap(),
+            vec![
+                Token {
+                    token_type: TokenType::Number,
+                    value: "0xFF".to_string()
+                },
+                Token {
+                    token_type: TokenType::Operator,
+                    value: "&".to_string()
+                },
+                Token {
+                    token_type: TokenType::Number,
+                    value: "0b1010".to_string()
+                },
+            ]
+        );
+
+        let rpn = tokens_to_rpn(Tokenizer::new().tokenize("0xFF").unwrap()).unwrap();
+        assert_eq!(rpn, vec![Value::Number(255.0)]);
+
+        let rpn = tokens_to_rpn(Tokenizer::new().tokenize("0b1010").unwrap()).unwrap();
+        assert_eq!(rpn, vec![Value::Number(10.0)]);
+    }
+
+    proptest! {
+        #[test]
+        fn test_tokenize_never_panics(input in "\\PC*") {
+            let _ = Tokenizer::new().tokenize(&input);
+        }
+
+        #[test]
+        fn test_tokenize_expression_like_never_panics(input in r"[0-9a-z{}()+\-*/&|<>=. \u{00A0}é]*") {
+            let _ = Tokenizer::new().tokenize(&input);
+        }
+    }
 }
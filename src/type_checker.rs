@@ -0,0 +1,280 @@
+use crate::parser::{FunctionType, OperatorType, Rpn, Value};
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum VarType {
+    Number,
+    Boolean,
+    List,
+}
+
+/// type of the value an expression evaluates to
+pub type ResultType = VarType;
+
+impl std::fmt::Display for VarType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VarType::Number => write!(f, "number"),
+            VarType::Boolean => write!(f, "boolean"),
+            VarType::List => write!(f, "list"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TypeError {
+    // name of the variable as parameter
+    UnknownVariable(String),
+    OperatorMismatch {
+        operator: OperatorType,
+        left: VarType,
+        right: VarType,
+    },
+    ArgumentMismatch {
+        function: FunctionType,
+        expected: VarType,
+        found: VarType,
+    },
+    // contains the type of a list element which is not a number
+    InvalidListElement(VarType),
+    NotEnoughValues,
+    TooMuchValues,
+    WrongResultType {
+        expected: ResultType,
+        found: ResultType,
+    },
+}
+
+impl std::fmt::Display for TypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypeError::UnknownVariable(name) => {
+                write!(f, "type of variable {name} is not declared")
+            }
+            TypeError::OperatorMismatch {
+                operator,
+                left,
+                right,
+            } => write!(
+                f,
+                "operator `{operator}` cannot be applied to {left} and {right}"
+            ),
+            TypeError::ArgumentMismatch {
+                function,
+                expected,
+                found,
+            } => write!(
+                f,
+                "function `{function}` expects {expected} arguments, found {found}"
+            ),
+            TypeError::InvalidListElement(found) => {
+                write!(f, "list elements have to be numbers, found {found}")
+            }
+            TypeError::NotEnoughValues => write!(f, "not enough values entered"),
+            TypeError::TooMuchValues => write!(f, "too much values entered"),
+            TypeError::WrongResultType { expected, found } => {
+                write!(f, "expected {expected} result, found {found}")
+            }
+        }
+    }
+}
+
+/// returns the type of the operator result, if it accepts the operand types
+fn operator_result_type(op: &OperatorType, left: VarType, right: VarType) -> Option<VarType> {
+    match (op, left, right) {
+        (
+            OperatorType::Plus
+            | OperatorType::Minus
+            | OperatorType::Times
+            | OperatorType::Divide
+            | OperatorType::BitAnd
+            | OperatorType::Custom { .. },
+            VarType::Number,
+            VarType::Number,
+        ) => Some(VarType::Number),
+        (
+            OperatorType::LT
+            | OperatorType::LE
+            | OperatorType::GT
+            | OperatorType::GE
+            | OperatorType::Eq,
+            VarType::Number,
+            VarType::Number,
+        ) => Some(VarType::Boolean),
+        (
+            OperatorType::And | OperatorType::Or | OperatorType::Implies,
+            VarType::Boolean,
+            VarType::Boolean,
+        ) => Some(VarType::Boolean),
+        (OperatorType::In, VarType::Number, VarType::List) => Some(VarType::Boolean),
+        // either operand can be the result
+        (OperatorType::Coalesce, left, right) if left == right => Some(left),
+        _ => None,
+    }
+}
+
+/// Runs the stack machine over types instead of values.
+pub fn check_types(
+    rpn: &Rpn,
+    var_types: &HashMap<String, VarType>,
+) -> Result<ResultType, TypeError> {
+    // stack - last index in, last index out
+    let mut type_stack: Vec<VarType> = vec![];
+
+    for value in rpn {
+        match value {
+            Value::Number(_) | Value::Placeholder(_) => type_stack.push(VarType::Number),
+            Value::Boolean(_) => type_stack.push(VarType::Boolean),
+            Value::Variable(name) => type_stack.push(
+                *var_types
+                    .get(name)
+                    .ok_or_else(|| TypeError::UnknownVariable(name.to_owned()))?,
+            ),
+            Value::Operator(op) if op.is_postfix() => match type_stack.last() {
+                Some(VarType::Number) => {}
+                Some(found) => {
+                    return Err(TypeError::OperatorMismatch {
+                        operator: op.clone(),
+                        left: *found,
+                        right: *found,
+                    })
+                }
+                None => return Err(TypeError::NotEnoughValues),
+            },
+            Value::Operator(op) => {
+                let right = type_stack.pop().ok_or(TypeError::NotEnoughValues)?;
+                let left = type_stack.pop().ok_or(TypeError::NotEnoughValues)?;
+
+                type_stack.push(operator_result_type(op, left, right).ok_or(
+                    TypeError::OperatorMismatch {
+                        operator: op.clone(),
+                        left,
+                        right,
+                    },
+                )?);
+            }
+            Value::List(len) => {
+                if type_stack.len() < *len {
+                    return Err(TypeError::NotEnoughValues);
+                }
+                if let Some(found) = type_stack
+                    .drain(type_stack.len() - len..)
+                    .find(|found| *found != VarType::Number)
+                {
+                    return Err(TypeError::InvalidListElement(found));
+                }
+                type_stack.push(VarType::List);
+            }
+            Value::Function(FunctionType::If, _) => {
+                let else_type = type_stack.pop().ok_or(TypeError::NotEnoughValues)?;
+                let then_type = type_stack.pop().ok_or(TypeError::NotEnoughValues)?;
+                let condition = type_stack.pop().ok_or(TypeError::NotEnoughValues)?;
+                if condition != VarType::Boolean {
+                    return Err(TypeError::ArgumentMismatch {
+                        function: FunctionType::If,
+                        expected: VarType::Boolean,
+                        found: condition,
+                    });
+                }
+                // both branches have to result in the same type
+                if then_type != else_type {
+                    return Err(TypeError::ArgumentMismatch {
+                        function: FunctionType::If,
+                        expected: then_type,
+                        found: else_type,
+                    });
+                }
+                type_stack.push(then_type);
+            }
+            Value::Function(function, arg_count) => {
+                if type_stack.len() < *arg_count {
+                    return Err(TypeError::NotEnoughValues);
+                }
+                // `first_true` takes conditions
+                let expected = match function {
+                    FunctionType::FirstTrue => VarType::Boolean,
+                    _ => VarType::Number,
+                };
+                for found in type_stack.drain(type_stack.len() - arg_count..) {
+                    if found != expected {
+                        return Err(TypeError::ArgumentMismatch {
+                            function: function.clone(),
+                            expected,
+                            found,
+                        });
+                    }
+                }
+                type_stack.push(function.result_type());
+            }
+        }
+    }
+
+    match type_stack[..] {
+        [result] => Ok(result),
+        [] => Err(TypeError::NotEnoughValues),
+        _ => Err(TypeError::TooMuchValues),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_types, TypeError, VarType};
+    use crate::parser::{OperatorType, Value};
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_check_types() {
+        // 1 + 2
+        let rpn = vec![
+            Value::Number(1.0),
+            Value::Number(2.0),
+            Value::Operator(OperatorType::Plus),
+        ];
+        assert_eq!(check_types(&rpn, &HashMap::new()), Ok(VarType::Number));
+
+        // x < 2 || flag
+        let rpn = vec![
+            Value::Variable("x".to_string()),
+            Value::Number(2.0),
+            Value::Operator(OperatorType::LT),
+            Value::Variable("flag".to_string()),
+            Value::Operator(OperatorType::Or),
+        ];
+        let var_types = HashMap::from_iter(vec![
+            ("x".to_string(), VarType::Number),
+            ("flag".to_string(), VarType::Boolean),
+        ]);
+        assert_eq!(check_types(&rpn, &var_types), Ok(VarType::Boolean));
+    }
+
+    #[test]
+    fn test_check_types_errors() {
+        // true + 1
+        let rpn = vec![
+            Value::Boolean(true),
+            Value::Number(1.0),
+            Value::Operator(OperatorType::Plus),
+        ];
+        assert_eq!(
+            check_types(&rpn, &HashMap::new()),
+            Err(TypeError::OperatorMismatch {
+                operator: OperatorType::Plus,
+                left: VarType::Boolean,
+                right: VarType::Number,
+            })
+        );
+
+        let rpn = vec![Value::Variable("x".to_string())];
+        assert_eq!(
+            check_types(&rpn, &HashMap::new()),
+            Err(TypeError::UnknownVariable("x".to_string()))
+        );
+
+        let rpn = vec![Value::Number(1.0), Value::Operator(OperatorType::Plus)];
+        assert_eq!(
+            check_types(&rpn, &HashMap::new()),
+            Err(TypeError::NotEnoughValues)
+        );
+    }
+}